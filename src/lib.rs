@@ -0,0 +1,3200 @@
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use plotters::prelude::*;
+use rustfft::{Fft, FftPlanner, num_complex::Complex32};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, f32::consts::PI, sync::Arc};
+use symphonia::core::{
+    audio::SampleBuffer, errors::Error as SymphoniaError, io::MediaSourceStream, probe::Hint,
+};
+
+pub mod pitch;
+pub mod scala;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+
+/// The twelve chromatic note names with their equal-tempered reference
+/// frequencies in the octave of middle C.
+pub static NOTES: [(&str, f32); 12] = [
+    ("C", 261.63),
+    ("C#", 277.18),
+    ("D", 293.66),
+    ("D#", 311.13),
+    ("E", 329.63),
+    ("F", 349.23),
+    ("F#", 369.99),
+    ("G", 392.00),
+    ("G#", 415.30),
+    ("A", 440.00),
+    ("A#", 466.16),
+    ("B", 493.88),
+];
+
+/// How accidentals are spelled in displayed note labels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum NoteSpelling {
+    Sharps,
+    UnicodeSharps,
+    Flats,
+}
+
+impl NoteSpelling {
+    pub const ALL: [NoteSpelling; 3] = [
+        NoteSpelling::Sharps,
+        NoteSpelling::UnicodeSharps,
+        NoteSpelling::Flats,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoteSpelling::Sharps => "Sharps (ASCII)",
+            NoteSpelling::UnicodeSharps => "Sharps (♯)",
+            NoteSpelling::Flats => "Flats (♭)",
+        }
+    }
+}
+
+/// Respell a note label such as "A#4" in the chosen accidental style.
+///
+/// `Sharps` keeps the ASCII label, `UnicodeSharps` swaps `#` for a proper
+/// sharp sign, and `Flats` renames each sharp to its enharmonic flat
+/// (A#4 becomes B♭4) for players thinking in flat keys. Both letters of
+/// every enharmonic pair lie in the same octave, so the octave digit
+/// carries over unchanged. Labels without a sharp pass through untouched.
+pub fn spell_note_label(label: &str, spelling: NoteSpelling) -> String {
+    match spelling {
+        NoteSpelling::Sharps => label.to_string(),
+        NoteSpelling::UnicodeSharps => label.replace('#', "♯"),
+        NoteSpelling::Flats => {
+            static FLATS: [(&str, &str); 5] = [
+                ("C#", "D♭"),
+                ("D#", "E♭"),
+                ("F#", "G♭"),
+                ("G#", "A♭"),
+                ("A#", "B♭"),
+            ];
+            for (sharp, flat) in FLATS {
+                if let Some(rest) = label.strip_prefix(sharp) {
+                    return format!("{}{}", flat, rest);
+                }
+            }
+            label.to_string()
+        }
+    }
+}
+
+/// Tuning system used to derive note target frequencies.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Temperament {
+    Equal,
+    Pythagorean,
+    QuarterCommaMeantone,
+    JustIntonation,
+    Werckmeister,
+}
+
+impl Temperament {
+    pub const ALL: [Temperament; 5] = [
+        Temperament::Equal,
+        Temperament::Pythagorean,
+        Temperament::QuarterCommaMeantone,
+        Temperament::JustIntonation,
+        Temperament::Werckmeister,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Temperament::Equal => "Equal temperament",
+            Temperament::Pythagorean => "Pythagorean",
+            Temperament::QuarterCommaMeantone => "Quarter-comma meantone",
+            Temperament::JustIntonation => "Just intonation",
+            Temperament::Werckmeister => "Werckmeister III",
+        }
+    }
+
+    /// Frequency ratios of the 12 chromatic degrees above the tonic.
+    pub fn ratios(&self) -> [f32; 12] {
+        match self {
+            Temperament::Equal => {
+                let mut ratios = [0.0f32; 12];
+                for (degree, ratio) in ratios.iter_mut().enumerate() {
+                    *ratio = 2f32.powf(degree as f32 / 12.0);
+                }
+                ratios
+            }
+            Temperament::Pythagorean => ratios_from_fifth(1.5),
+            Temperament::QuarterCommaMeantone => ratios_from_fifth(5f32.powf(0.25)),
+            Temperament::JustIntonation => [
+                1.0,
+                16.0 / 15.0,
+                9.0 / 8.0,
+                6.0 / 5.0,
+                5.0 / 4.0,
+                4.0 / 3.0,
+                45.0 / 32.0,
+                3.0 / 2.0,
+                8.0 / 5.0,
+                5.0 / 3.0,
+                9.0 / 5.0,
+                15.0 / 8.0,
+            ],
+            // Werckmeister's "correct temperament" No. 1 (usually cited
+            // as Werckmeister III): four fifths (on C, G, D and B)
+            // narrowed by a quarter of the Pythagorean comma, the rest
+            // pure, which closes the circle and keeps every key usable.
+            Temperament::Werckmeister => [
+                1.0,
+                256.0 / 243.0,
+                64.0 / 81.0 * 2f32.sqrt(),
+                32.0 / 27.0,
+                256.0 / 243.0 * 2f32.powf(0.25),
+                4.0 / 3.0,
+                1024.0 / 729.0,
+                8.0 / 9.0 * 2f32.powf(0.75),
+                128.0 / 81.0,
+                1024.0 / 729.0 * 2f32.powf(0.25),
+                16.0 / 9.0,
+                128.0 / 81.0 * 2f32.powf(0.25),
+            ],
+        }
+    }
+}
+
+// Build the chromatic ratio table from a chain of fifths, reducing each
+// ratio back into a single octave. The chain positions are the number of
+// fifths above (positive) or below (negative) the tonic for each degree.
+fn ratios_from_fifth(fifth: f32) -> [f32; 12] {
+    const FIFTH_CHAIN: [i32; 12] = [0, -5, 2, -3, 4, -1, 6, 1, -4, 3, -2, 5];
+    let mut ratios = [0.0f32; 12];
+    for (degree, &steps) in FIFTH_CHAIN.iter().enumerate() {
+        let mut ratio = fifth.powi(steps);
+        while ratio < 1.0 {
+            ratio *= 2.0;
+        }
+        while ratio >= 2.0 {
+            ratio /= 2.0;
+        }
+        ratios[degree] = ratio;
+    }
+    ratios
+}
+
+/// Target frequencies for the 12 notes in the NOTES reference octave under
+/// the given temperament, anchored so the tonic keeps its equal-tempered
+/// frequency. Entries are in NOTES order (C..B).
+pub fn note_frequencies(temperament: Temperament, tonic: usize) -> [f32; 12] {
+    let ratios = temperament.ratios();
+    let tonic_freq = NOTES[tonic].1;
+    let mut frequencies = [0.0f32; 12];
+    for (i, freq) in frequencies.iter_mut().enumerate() {
+        let degree = (i + 12 - tonic) % 12;
+        let mut candidate = tonic_freq * ratios[degree];
+        // Fold into the octave of the note's equal-tempered counterpart so
+        // each entry keeps its conventional name.
+        let reference = NOTES[i].1;
+        while candidate / reference > 2f32.sqrt() {
+            candidate /= 2.0;
+        }
+        while reference / candidate > 2f32.sqrt() {
+            candidate *= 2.0;
+        }
+        *freq = candidate;
+    }
+    frequencies
+}
+
+/// Rescale a reference-octave table from concert A = 440 Hz to another A4
+/// reference. Every target moves by the same ratio, so interval structure
+/// — and therefore the temperament — is preserved; only the pitch level
+/// shifts, which is exactly what a baroque ensemble at A=415 or an
+/// orchestra at 442 wants.
+pub fn apply_a4_reference(frequencies: [f32; 12], a4: f32) -> [f32; 12] {
+    // One ratio, applied once, so 440 stays a bit-exact identity.
+    let ratio = a4 / 440.0;
+    frequencies.map(|freq| freq * ratio)
+}
+
+/// Shift a note label like "A#3" by the given number of semitones, wrapping
+/// octaves as needed. Labels that don't parse are returned unchanged.
+pub fn transpose_note_label(label: &str, semitones: i32) -> String {
+    let split = label.find(|c: char| c.is_ascii_digit() || c == '-');
+    let Some(split) = split else {
+        return label.to_string();
+    };
+    let (name, octave) = label.split_at(split);
+    let Some(note_index) = NOTES.iter().position(|(n, _)| *n == name) else {
+        return label.to_string();
+    };
+    let Ok(octave) = octave.parse::<i32>() else {
+        return label.to_string();
+    };
+    let total = octave * 12 + note_index as i32 + semitones;
+    let new_octave = total.div_euclid(12);
+    let new_index = total.rem_euclid(12) as usize;
+    format!("{}{}", NOTES[new_index].0, new_octave)
+}
+
+/// Open-string targets for a common instrument tuning.
+pub struct InstrumentPreset {
+    pub name: &'static str,
+    pub strings: &'static [(&'static str, f32)],
+}
+
+/// Presets covering the usual string instruments, low string first.
+pub static INSTRUMENT_PRESETS: [InstrumentPreset; 4] = [
+    InstrumentPreset {
+        name: "Guitar (EADGBE)",
+        strings: &[
+            ("E2", 82.41),
+            ("A2", 110.00),
+            ("D3", 146.83),
+            ("G3", 196.00),
+            ("B3", 246.94),
+            ("E4", 329.63),
+        ],
+    },
+    InstrumentPreset {
+        name: "Bass (EADG)",
+        strings: &[
+            ("E1", 41.20),
+            ("A1", 55.00),
+            ("D2", 73.42),
+            ("G2", 98.00),
+        ],
+    },
+    InstrumentPreset {
+        name: "Violin (GDAE)",
+        strings: &[
+            ("G3", 196.00),
+            ("D4", 293.66),
+            ("A4", 440.00),
+            ("E5", 659.25),
+        ],
+    },
+    InstrumentPreset {
+        name: "Ukulele (GCEA)",
+        strings: &[
+            ("G4", 392.00),
+            ("C4", 261.63),
+            ("E4", 329.63),
+            ("A4", 440.00),
+        ],
+    },
+];
+
+/// Snap a detected frequency to the closest open string of the preset,
+/// returning the string's label and target frequency.
+pub fn nearest_preset_string(freq: f32, preset: &InstrumentPreset) -> Option<(&'static str, f32)> {
+    if freq <= 0.0 {
+        return None;
+    }
+    preset
+        .strings
+        .iter()
+        .map(|&(label, target)| (label, target))
+        .min_by(|a, b| {
+            let diff_a = cents_offset(freq, a.1).abs();
+            let diff_b = cents_offset(freq, b.1).abs();
+            diff_a
+                .partial_cmp(&diff_b)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// How the fundamental is picked from the averaged magnitude spectrum.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DetectionMethod {
+    SpectralPeak,
+    HarmonicProduct,
+    Cepstrum,
+    ZeroCrossing,
+    Yin,
+    Mpm,
+}
+
+impl DetectionMethod {
+    pub const ALL: [DetectionMethod; 6] = [
+        DetectionMethod::SpectralPeak,
+        DetectionMethod::HarmonicProduct,
+        DetectionMethod::Cepstrum,
+        DetectionMethod::ZeroCrossing,
+        DetectionMethod::Yin,
+        DetectionMethod::Mpm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DetectionMethod::SpectralPeak => "Spectral peak",
+            DetectionMethod::HarmonicProduct => "Harmonic product",
+            DetectionMethod::Cepstrum => "Cepstrum",
+            DetectionMethod::ZeroCrossing => "Zero crossing (low CPU)",
+            DetectionMethod::Yin => "YIN (time domain)",
+            DetectionMethod::Mpm => "MPM (time domain)",
+        }
+    }
+}
+
+/// How the per-hop magnitude spectra of one analysis pass are combined
+/// into a single spectrum.
+///
+/// With overlapping hops the frames are not independent, so a plain mean
+/// double-counts transient energy during attacks. The median is robust to
+/// a single transient-heavy frame at the cost of some responsiveness, and
+/// the central frame is the most responsive but also the noisiest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FrameAggregation {
+    Mean,
+    Median,
+    CentralFrame,
+    SingleFrame,
+}
+
+impl FrameAggregation {
+    pub const ALL: [FrameAggregation; 4] = [
+        FrameAggregation::Mean,
+        FrameAggregation::Median,
+        FrameAggregation::CentralFrame,
+        FrameAggregation::SingleFrame,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrameAggregation::Mean => "Mean",
+            FrameAggregation::Median => "Median",
+            FrameAggregation::CentralFrame => "Central frame",
+            FrameAggregation::SingleFrame => "Single frame (fast, noisier)",
+        }
+    }
+}
+
+/// Combine per-frame magnitude spectra into one spectrum per bin using the
+/// chosen strategy. All frames must share a length; returns an empty Vec
+/// when there are no frames.
+pub fn aggregate_magnitudes(frames: &[Vec<f32>], strategy: FrameAggregation) -> Vec<f32> {
+    let Some(first) = frames.first() else {
+        return Vec::new();
+    };
+    let num_bins = first.len();
+    match strategy {
+        FrameAggregation::Mean => {
+            let mut combined = vec![0.0f32; num_bins];
+            for frame in frames {
+                for (bin, magnitude) in frame.iter().enumerate() {
+                    combined[bin] += magnitude;
+                }
+            }
+            for magnitude in &mut combined {
+                *magnitude /= frames.len() as f32;
+            }
+            combined
+        }
+        FrameAggregation::Median => (0..num_bins)
+            .map(|bin| {
+                let values: Vec<f32> = frames.iter().map(|frame| frame[bin]).collect();
+                median(&values)
+            })
+            .collect(),
+        FrameAggregation::CentralFrame => frames[frames.len() / 2].clone(),
+        // Callers that already have frames take the newest one; the live
+        // path skips the multi-frame transform via
+        // [`single_frame_magnitudes`] instead.
+        FrameAggregation::SingleFrame => frames[frames.len() - 1].clone(),
+    }
+}
+
+/// Flatten the spectral envelope by dividing each magnitude by a local
+/// moving average over `smoothing_bins` neighbors, so peak picking compares
+/// peaks against their local noise floor rather than against the absolute
+/// energy of other registers. Instruments with a steep roll-off otherwise
+/// bias the argmax toward whichever partial rides the loudest part of the
+/// envelope. Bins whose neighborhood carries no energy are left unchanged
+/// instead of being divided by ~0.
+pub fn whiten_spectrum(magnitudes: &mut [f32], smoothing_bins: usize) {
+    if magnitudes.is_empty() || smoothing_bins == 0 {
+        return;
+    }
+    let half = smoothing_bins / 2;
+    let envelope: Vec<f32> = (0..magnitudes.len())
+        .map(|i| {
+            let start = i.saturating_sub(half);
+            let end = (i + half + 1).min(magnitudes.len());
+            magnitudes[start..end].iter().sum::<f32>() / (end - start) as f32
+        })
+        .collect();
+    for (magnitude, envelope_level) in magnitudes.iter_mut().zip(envelope) {
+        if envelope_level > f32::EPSILON {
+            *magnitude /= envelope_level;
+        }
+    }
+}
+
+/// Local maxima of a magnitude spectrum that clear a prominence and a
+/// spacing threshold, ordered most prominent first. Prominence is the
+/// height of a peak above the higher of the two valleys separating it
+/// from larger neighbors (or from the spectrum edge), which is what
+/// distinguishes a real partial from the jagged local maxima riding on a
+/// broad noise hump: those rise only a little above their immediate
+/// surroundings however loud the hump is. Peaks closer than
+/// `min_distance_bins` to an already-accepted, more prominent peak are
+/// dropped. Bin 0 is never a peak: it holds whatever DC survives mean
+/// removal.
+pub fn find_peaks(
+    magnitudes: &[f32],
+    min_prominence: f32,
+    min_distance_bins: usize,
+) -> Vec<usize> {
+    let mut candidates: Vec<(usize, f32)> = Vec::new();
+    for bin in 1..magnitudes.len().saturating_sub(1) {
+        if magnitudes[bin] <= magnitudes[bin - 1] || magnitudes[bin] < magnitudes[bin + 1] {
+            continue;
+        }
+        let height = magnitudes[bin];
+        // Walk outward until a taller bin (or the edge); the lowest point
+        // passed on each side is that side's base.
+        let mut left_base = height;
+        for &value in magnitudes[..bin].iter().rev() {
+            if value > height {
+                break;
+            }
+            left_base = left_base.min(value);
+        }
+        let mut right_base = height;
+        for &value in &magnitudes[bin + 1..] {
+            if value > height {
+                break;
+            }
+            right_base = right_base.min(value);
+        }
+        let prominence = height - left_base.max(right_base);
+        if prominence >= min_prominence {
+            candidates.push((bin, prominence));
+        }
+    }
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut peaks: Vec<usize> = Vec::new();
+    for (bin, _) in candidates {
+        if peaks
+            .iter()
+            .all(|&kept| bin.abs_diff(kept) >= min_distance_bins)
+        {
+            peaks.push(bin);
+        }
+    }
+    peaks
+}
+
+/// Inharmonicity coefficient B of a struck or plucked string, fitted to
+/// the stretched-partial model `f_n = n·f1·sqrt(1 + B·n²)`. Stiff string
+/// cores — pianos especially — push overtones progressively sharp of the
+/// exact integer multiples, which is why piano tuners stretch octaves.
+/// Each partial from the 2nd to the 8th is located as the strongest bin
+/// near its ideal position (with a search window that widens for higher
+/// partials, since the stretch grows with n), and B comes from a
+/// least-squares fit of `(f_n / (n·f1))² - 1` against `n²`. Returns None
+/// when fewer than three partials rise above 2% of the fundamental's
+/// magnitude: too little evidence for a fit.
+pub fn estimate_inharmonicity(
+    magnitudes: &[f32],
+    fundamental: f32,
+    freq_resolution: f32,
+) -> Option<f32> {
+    if fundamental <= 0.0 || freq_resolution <= 0.0 || magnitudes.is_empty() {
+        return None;
+    }
+    let fundamental_bin = (fundamental / freq_resolution).round() as usize;
+    let floor = magnitudes.get(fundamental_bin).copied().unwrap_or(0.0) * 0.02;
+    let mut numerator = 0.0f64;
+    let mut denominator = 0.0f64;
+    let mut measured = 0usize;
+    for n in 2..=8usize {
+        let center = (n as f32 * fundamental / freq_resolution).round() as isize;
+        let half = (n as isize / 2).max(2);
+        let low = (center - half).max(0) as usize;
+        let high = ((center + half) as usize).min(magnitudes.len().saturating_sub(1));
+        if low >= high {
+            break;
+        }
+        let (peak_bin, peak_magnitude) = magnitudes[low..=high]
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(offset, &magnitude)| (low + offset, magnitude))?;
+        if peak_magnitude < floor || peak_magnitude <= 0.0 {
+            continue;
+        }
+        let ratio = (peak_bin as f32 * freq_resolution) / (n as f32 * fundamental);
+        let n_squared = (n * n) as f64;
+        numerator += ((ratio as f64).powi(2) - 1.0) * n_squared;
+        denominator += n_squared * n_squared;
+        measured += 1;
+    }
+    if measured < 3 {
+        return None;
+    }
+    Some((numerator / denominator) as f32)
+}
+
+/// First-order pre-emphasis filter `y[n] = x[n] - a * x[n-1]`, the standard
+/// speech-processing step for tilting the spectrum upward: its response is
+/// near zero at DC and rises toward the Nyquist frequency. High notes have
+/// weak fundamentals relative to low-frequency room noise, so running the
+/// window through this before the STFT makes treble pitches stand out in
+/// the magnitude argmax. A coefficient around 0.95 is conventional; 0
+/// passes the signal through unchanged.
+pub fn pre_emphasis(samples: &[f32], coefficient: f32) -> Vec<f32> {
+    let mut previous = 0.0;
+    samples
+        .iter()
+        .map(|&sample| {
+            let emphasized = sample - coefficient * previous;
+            previous = sample;
+            emphasized
+        })
+        .collect()
+}
+
+/// One step of the exponential smoothing applied to the displayed
+/// spectrum: `smoothed = alpha·new + (1 - alpha)·smoothed` per bin, in
+/// place. Successive STFT frames are independent, so the raw plot
+/// flickers; blending each new frame into the previous display steadies
+/// it without touching the detection path, which keeps using the raw
+/// magnitudes. An alpha of 1 disables the smoothing; a size change (e.g.
+/// a window-size switch) resets to the new frame outright.
+pub fn smooth_spectrum(smoothed: &mut Vec<f32>, new: &[f32], alpha: f32) {
+    if smoothed.len() != new.len() {
+        *smoothed = new.to_vec();
+        return;
+    }
+    for (old, &fresh) in smoothed.iter_mut().zip(new) {
+        *old = alpha * fresh + (1.0 - alpha) * *old;
+    }
+}
+
+/// Window length appropriate to a detected fundamental, for the adaptive
+/// analysis mode: long windows for bass notes, where neighboring semitones
+/// are only a few Hz apart, down to short ones in the treble, where the
+/// wide semitone spacing makes the extra resolution pure latency.
+///
+/// To keep a note wavering around a band boundary from oscillating between
+/// two sizes, the current size wins whenever the frequency is within about
+/// a semitone of a boundary; only a pitch clearly inside another band
+/// switches the window.
+pub fn adaptive_window_size(frequency: f32, current: usize) -> usize {
+    fn band(frequency: f32) -> usize {
+        if frequency < 110.0 {
+            8192
+        } else if frequency < 440.0 {
+            4096
+        } else if frequency < 1760.0 {
+            2048
+        } else {
+            1024
+        }
+    }
+    if frequency <= 0.0 {
+        return current;
+    }
+    // One equal-tempered semitone either side; inside that margin of a
+    // boundary the two probes disagree and we stay put.
+    let margin = 2f32.powf(1.0 / 12.0);
+    if band(frequency * margin) == band(frequency / margin) {
+        band(frequency)
+    } else {
+        current
+    }
+}
+
+/// Magnitude spectrum of one windowed FFT over the newest `window_size`
+/// samples, for the single-frame detection mode. More responsive than
+/// multi-frame aggregation because nothing older than one window
+/// influences the result, and noisier for the same reason.
+pub fn single_frame_magnitudes(buffer: &[f32], window_size: usize) -> Vec<f32> {
+    if window_size == 0 || buffer.len() < window_size {
+        return Vec::new();
+    }
+    let start = buffer.len() - window_size;
+    compute_short_time_fourier_transform(&buffer[start..], window_size, window_size)
+        .first()
+        .map(|frame| frame[..window_size / 2].iter().map(|v| v.norm()).collect())
+        .unwrap_or_default()
+}
+
+/// Downsample the spectrum by each harmonic index and multiply, which
+/// reinforces the fundamental even when a harmonic carries more energy.
+pub fn harmonic_product_spectrum(magnitudes: &[f32], num_harmonics: usize) -> Vec<f32> {
+    if magnitudes.is_empty() || num_harmonics == 0 {
+        return Vec::new();
+    }
+    let product_len = magnitudes.len() / num_harmonics.max(1);
+    let mut product = vec![1.0f32; product_len];
+    for harmonic in 1..=num_harmonics {
+        for (bin, value) in product.iter_mut().enumerate() {
+            *value *= magnitudes[bin * harmonic];
+        }
+    }
+    product
+}
+
+/// Refine an FFT peak to a fractional bin by fitting a parabola through
+/// the peak and its two neighbours. A 4096-point window at 48 kHz has
+/// ~11.7 Hz bins — half a semitone at low pitches — while the vertex of
+/// the fitted parabola lands within a couple of cents of the true
+/// frequency. Edge bins and flat neighbourhoods are returned unshifted,
+/// and the offset is clamped to ±0.5 bins so a bad fit can never jump to
+/// a different bin.
+pub fn refine_peak_bin(magnitudes: &[f32], bin: usize) -> f32 {
+    if bin == 0 || bin + 1 >= magnitudes.len() {
+        return bin as f32;
+    }
+    let left = magnitudes[bin - 1];
+    let center = magnitudes[bin];
+    let right = magnitudes[bin + 1];
+    let denominator = left - 2.0 * center + right;
+    if denominator.abs() <= f32::EPSILON {
+        return bin as f32;
+    }
+    let offset = (0.5 * (left - right) / denominator).clamp(-0.5, 0.5);
+    bin as f32 + offset
+}
+
+/// Convert an i16 sample to f32, matching the normalization in read_wav.
+pub fn i16_sample_to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+/// Convert an unsigned 16-bit sample to f32 centered on zero.
+pub fn u16_sample_to_f32(sample: u16) -> f32 {
+    (sample as f32 - 32768.0) / 32768.0
+}
+
+/// Average interleaved channel samples down to a single mono stream.
+pub fn downmix_to_mono(interleaved: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// How interleaved input is reduced to the analysis mono stream.
+///
+/// `Mix` averages every channel; the others pick one channel out of the
+/// interleaved frames, which is what you want when the instrument mic is
+/// on one channel and the other carries bleed or noise. `Index` counts
+/// from zero.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ChannelSelection {
+    Mix,
+    Left,
+    Right,
+    Index(usize),
+}
+
+/// Reduce interleaved samples to mono according to the channel selection.
+/// A selected channel beyond the device's count falls back to the mix, so
+/// a stale setting degrades to the old behavior instead of to silence.
+pub fn select_channel(
+    interleaved: &[f32],
+    channels: usize,
+    selection: ChannelSelection,
+) -> Vec<f32> {
+    if channels <= 1 {
+        return interleaved.to_vec();
+    }
+    let index = match selection {
+        ChannelSelection::Mix => return downmix_to_mono(interleaved, channels),
+        ChannelSelection::Left => 0,
+        ChannelSelection::Right => 1,
+        ChannelSelection::Index(index) => index,
+    };
+    if index >= channels {
+        return downmix_to_mono(interleaved, channels);
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame[index])
+        .collect()
+}
+
+/// One detected pitch observation on the session timeline.
+pub struct PitchRecord {
+    pub timestamp_seconds: f32,
+    pub frequency: f32,
+    pub note: String,
+    pub cents_offset: f32,
+}
+
+/// Write accumulated pitch records as CSV with a matching header row.
+pub fn write_pitch_track_csv(
+    path: &str,
+    records: &[PitchRecord],
+    stats: Option<&DriftStats>,
+) -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    let file = std::fs::File::create(path)?;
+    let mut writer = std::io::BufWriter::new(file);
+    writeln!(writer, "timestamp_seconds,detected_freq,note_name,cents_offset")?;
+    for record in records {
+        writeln!(
+            writer,
+            "{:.4},{:.3},{},{:.2}",
+            record.timestamp_seconds, record.frequency, record.note, record.cents_offset
+        )?;
+    }
+    if let Some(stats) = stats {
+        // Session summary as comment lines, so row parsers can skip them.
+        writeln!(
+            writer,
+            "# mean_cents={:.2} std_dev_cents={:.2} in_tune_percent={:.1}",
+            stats.mean(),
+            stats.std_dev(),
+            stats.in_tune_percent()
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Running statistics over the cents offsets seen in a session: mean,
+/// standard deviation, and the share of readings within ±5 cents.
+/// Accumulated in constant space with Welford's algorithm so the analysis
+/// thread can push one value per frame indefinitely.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DriftStats {
+    count: usize,
+    mean: f32,
+    sum_squared_deltas: f32,
+    in_tune: usize,
+}
+
+impl DriftStats {
+    pub fn new() -> Self {
+        DriftStats::default()
+    }
+
+    /// Fold one cents reading into the running statistics.
+    pub fn push(&mut self, cents: f32) {
+        self.count += 1;
+        let delta = cents - self.mean;
+        self.mean += delta / self.count as f32;
+        self.sum_squared_deltas += delta * (cents - self.mean);
+        if cents.abs() <= 5.0 {
+            self.in_tune += 1;
+        }
+    }
+
+    /// Discard everything, e.g. when a new take starts.
+    pub fn clear(&mut self) {
+        *self = DriftStats::default();
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    /// Sample standard deviation; 0 until there are two readings.
+    pub fn std_dev(&self) -> f32 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        (self.sum_squared_deltas / (self.count - 1) as f32).sqrt()
+    }
+
+    /// Percentage of readings within ±5 cents; 0 while empty.
+    pub fn in_tune_percent(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.in_tune as f32 * 100.0 / self.count as f32
+    }
+}
+
+/// Clarity of a magnitude spectrum as the ratio of the peak magnitude to
+/// the mean magnitude. A clean tone scores far above 1.0; flat noise
+/// stays close to 1.0.
+pub fn spectral_clarity(magnitudes: &[f32]) -> f32 {
+    if magnitudes.is_empty() {
+        return 0.0;
+    }
+    let peak = magnitudes.iter().fold(0.0f32, |acc, m| acc.max(*m));
+    let mean = magnitudes.iter().sum::<f32>() / magnitudes.len() as f32;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    peak / mean
+}
+
+/// Median of a slice, or 0.0 when empty.
+pub fn median(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    sorted[sorted.len() / 2]
+}
+
+/// Signed offset of freq from target in cents.
+pub fn cents_offset(freq: f32, target: f32) -> f32 {
+    if freq <= 0.0 || target <= 0.0 {
+        return 0.0;
+    }
+    1200.0 * (freq / target).log2()
+}
+
+/// Root-mean-square level of a sample buffer.
+pub fn rms(buffer: &[f32]) -> f32 {
+    if buffer.is_empty() {
+        return 0.0;
+    }
+    (buffer.iter().map(|s| s * s).sum::<f32>() / buffer.len() as f32).sqrt()
+}
+
+/// Whether the buffer shows sustained clipping: over 2% of samples at or
+/// near full scale (|s| >= 0.999). The flattened peaks add harmonic
+/// distortion that skews pitch detection, so callers should warn rather
+/// than silently report a dubious reading. A single brief transient at
+/// full scale stays under the threshold and is not flagged.
+pub fn is_clipping(buffer: &[f32]) -> bool {
+    if buffer.is_empty() {
+        return false;
+    }
+    let saturated = buffer.iter().filter(|s| s.abs() >= 0.999).count();
+    saturated as f32 / buffer.len() as f32 > 0.02
+}
+
+/// Map a frequency to the nearest note under the active temperament,
+/// returning the label (e.g. "A4") and the note's target frequency.
+///
+/// The octave number comes from the semitone distance to C4, so names roll
+/// over at each C per scientific pitch notation (C4, B4, C5) regardless of
+/// how the temperament bends individual targets. The search spans octaves
+/// -1 through 9 (the MIDI range); anything beyond returns None rather than
+/// clamping to an edge note.
+pub fn frequency_to_note(
+    freq: f32,
+    temperament: Temperament,
+    tonic: usize,
+) -> Option<(String, f32)> {
+    if freq <= 0.0 {
+        return None;
+    }
+    let frequencies = note_frequencies(temperament, tonic);
+    let nearest = (12.0 * (freq / NOTES[0].1).log2()).round() as i32;
+    // Tempered targets sit within a fraction of a semitone of their equal
+    // counterparts, so checking the rounded position and both neighbors is
+    // enough to find the closest target in cents.
+    let mut best: Option<(i32, f32)> = None;
+    for candidate in [nearest - 1, nearest, nearest + 1] {
+        let octave = 4 + candidate.div_euclid(12);
+        if !(-1..10).contains(&octave) {
+            continue;
+        }
+        let index = candidate.rem_euclid(12) as usize;
+        let target = frequencies[index] * 2f32.powi(octave - 4);
+        let closer = match best {
+            Some((_, current)) => {
+                cents_offset(freq, target).abs() < cents_offset(freq, current).abs()
+            }
+            None => true,
+        };
+        if closer {
+            best = Some((candidate, target));
+        }
+    }
+    best.map(|(position, target)| {
+        let octave = 4 + position.div_euclid(12);
+        let index = position.rem_euclid(12) as usize;
+        (format!("{}{}", NOTES[index].0, octave), target)
+    })
+}
+
+/// [`frequency_to_note`] against a chosen A4 reference instead of concert
+/// 440. The lookup runs in 440-space and the returned target is scaled
+/// back, so the label, the actual-Hz target, and the cents offset all
+/// agree with the chosen reference.
+pub fn frequency_to_note_at(
+    freq: f32,
+    temperament: Temperament,
+    tonic: usize,
+    a4: f32,
+) -> Option<(String, f32)> {
+    if a4 <= 0.0 {
+        return None;
+    }
+    let ratio = a4 / 440.0;
+    frequency_to_note(freq / ratio, temperament, tonic)
+        .map(|(note, target)| (note, target * ratio))
+}
+
+/// Precomputed note targets for one temperament and tonic, sorted by
+/// frequency for nearest-neighbor lookup.
+///
+/// [`frequency_to_note`] recomputes its candidate targets on every call;
+/// the analysis thread instead builds this table once per temperament
+/// change and binary-searches it per frame. Lookups agree with
+/// [`frequency_to_note`] across the octave -1..=9 range.
+pub struct NoteTable {
+    entries: Vec<(f32, String)>,
+}
+
+impl NoteTable {
+    /// Build the sorted table of every note target in octaves -1 through 9.
+    pub fn new(temperament: Temperament, tonic: usize) -> Self {
+        NoteTable::with_a4(temperament, tonic, 440.0)
+    }
+
+    /// Like [`NoteTable::new`] but with every target rescaled to the given
+    /// A4 reference.
+    pub fn with_a4(temperament: Temperament, tonic: usize, a4: f32) -> Self {
+        let frequencies = apply_a4_reference(note_frequencies(temperament, tonic), a4);
+        let mut entries = Vec::with_capacity(12 * 11);
+        for octave in -1i32..10 {
+            for (index, (name, _)) in NOTES.iter().enumerate() {
+                let target = frequencies[index] * 2f32.powi(octave - 4);
+                entries.push((target, format!("{}{}", name, octave)));
+            }
+        }
+        // Tempered targets can reorder slightly around octave seams, so
+        // sort by frequency rather than assuming generation order.
+        entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+        NoteTable { entries }
+    }
+
+    /// Nearest note to the frequency by cents, or None below 0 Hz or more
+    /// than a good margin past either end of the table — the same "don't
+    /// clamp to an edge note" behavior as [`frequency_to_note`].
+    pub fn lookup(&self, freq: f32) -> Option<(String, f32)> {
+        if freq <= 0.0 {
+            return None;
+        }
+        let position = self.entries.partition_point(|(target, _)| *target < freq);
+        // The nearest target is one of the two entries straddling freq.
+        let mut best: Option<&(f32, String)> = None;
+        for candidate in [position.checked_sub(1), Some(position)] {
+            if let Some(entry) = candidate.and_then(|index| self.entries.get(index)) {
+                let closer = match best {
+                    Some(current) => {
+                        cents_offset(freq, entry.0).abs() < cents_offset(freq, current.0).abs()
+                    }
+                    None => true,
+                };
+                if closer {
+                    best = Some(entry);
+                }
+            }
+        }
+        let (target, name) = best?;
+        if cents_offset(freq, *target).abs() > 150.0 {
+            return None;
+        }
+        Some((name.clone(), *target))
+    }
+}
+
+/// Label for a step of an N equal-divisions-of-the-octave scale, counted
+/// from C4 (negative steps reach lower octaves).
+///
+/// Steps landing on a 12-EDO semitone use the plain note name, exact
+/// quarter tones are spelled "half-sharp" above the note below (e.g.
+/// "A4 half-sharp" in 24-EDO), and anything else is the nearest semitone
+/// annotated with its offset in cents, as used in 19- or 31-EDO.
+pub fn edo_note_label(step: i32, divisions: usize) -> String {
+    let octave = 4 + step.div_euclid(divisions as i32);
+    let step_in_octave = step.rem_euclid(divisions as i32);
+    let semitones = step_in_octave as f32 * 12.0 / divisions as f32;
+    let lower = semitones.floor() as usize;
+    let fraction = semitones - lower as f32;
+    if fraction < 0.01 {
+        return format!("{}{}", NOTES[lower % 12].0, octave);
+    }
+    if (fraction - 0.5).abs() < 0.01 {
+        return format!("{}{} half-sharp", NOTES[lower % 12].0, octave);
+    }
+    let nearest = semitones.round() as usize;
+    let deviation_cents = (semitones - nearest as f32) * 100.0;
+    let (name, octave) = if nearest == 12 {
+        (NOTES[0].0, octave + 1)
+    } else {
+        (NOTES[nearest].0, octave)
+    };
+    format!("{}{} {:+.0}c", name, octave, deviation_cents)
+}
+
+/// Map a frequency to the nearest step of an N-EDO scale anchored at the
+/// equal-tempered C4, returning the label and the step's exact frequency.
+/// Matches [`frequency_to_note`] for 12 divisions and equal temperament.
+pub fn frequency_to_edo_note(freq: f32, divisions: usize) -> Option<(String, f32)> {
+    if freq <= 0.0 || divisions == 0 {
+        return None;
+    }
+    let c4 = NOTES[0].1;
+    let step = (divisions as f32 * (freq / c4).log2()).round() as i32;
+    let octave = 4 + step.div_euclid(divisions as i32);
+    if !(0..8).contains(&octave) {
+        return None;
+    }
+    let target = c4 * 2f32.powf(step as f32 / divisions as f32);
+    Some((edo_note_label(step, divisions), target))
+}
+
+/// Magnitude in decibels relative to the given reference level.
+pub fn to_db(magnitude: f32, reference: f32) -> f32 {
+    20.0 * (magnitude.max(1e-10) / reference.max(1e-10)).log10()
+}
+
+/// A-weighting gain in dB at the given frequency per IEC 61672, roughly
+/// matching the ear's sensitivity curve; close to 0 dB at 1 kHz and
+/// strongly negative at the spectrum extremes.
+pub fn a_weight(freq: f32) -> f32 {
+    if freq <= 0.0 {
+        return -120.0;
+    }
+    let f2 = freq * freq;
+    let ra = (12194.0f32.powi(2) * f2 * f2)
+        / ((f2 + 20.6f32.powi(2))
+            * ((f2 + 107.7f32.powi(2)) * (f2 + 737.9f32.powi(2))).sqrt()
+            * (f2 + 12194.0f32.powi(2)));
+    20.0 * ra.log10() + 2.0
+}
+
+/// Zero all spectrum bins whose center frequency falls outside the given
+/// band, so rumble and hiss outside an instrument's range can't win the
+/// peak search.
+pub fn band_limit(
+    magnitudes: &mut [f32],
+    sample_rate: usize,
+    window_size: usize,
+    min_hz: f32,
+    max_hz: f32,
+) {
+    if window_size == 0 {
+        return;
+    }
+    let bin_width = sample_rate as f32 / window_size as f32;
+    for (bin, magnitude) in magnitudes.iter_mut().enumerate() {
+        let freq = bin as f32 * bin_width;
+        if freq < min_hz || freq > max_hz {
+            *magnitude = 0.0;
+        }
+    }
+}
+
+/// Zero the bins within `width_hz` of `center_hz`, e.g. to notch out
+/// 50 or 60 Hz mains hum before the peak search. The fundamental alone is
+/// removed; callers worried about hum harmonics can notch multiples too.
+pub fn notch_out(
+    magnitudes: &mut [f32],
+    sample_rate: usize,
+    window_size: usize,
+    center_hz: f32,
+    width_hz: f32,
+) {
+    if window_size == 0 {
+        return;
+    }
+    let bin_width = sample_rate as f32 / window_size as f32;
+    for (bin, magnitude) in magnitudes.iter_mut().enumerate() {
+        let freq = bin as f32 * bin_width;
+        if (freq - center_hz).abs() <= width_hz {
+            *magnitude = 0.0;
+        }
+    }
+}
+
+/// Indices of the two strongest local maxima in the spectrum, strongest
+/// first. A secondary peak is only reported when it carries at least a
+/// tenth of the primary peak's magnitude, so noise floors don't masquerade
+/// as a second note.
+pub fn top_two_peaks(magnitudes: &[f32]) -> Vec<usize> {
+    let mut peaks: Vec<usize> = (1..magnitudes.len().saturating_sub(1))
+        .filter(|&bin| {
+            magnitudes[bin] > magnitudes[bin - 1] && magnitudes[bin] >= magnitudes[bin + 1]
+        })
+        .collect();
+    peaks.sort_by(|a, b| magnitudes[*b].total_cmp(&magnitudes[*a]));
+    peaks.truncate(2);
+    if peaks.len() == 2 && magnitudes[peaks[1]] < 0.1 * magnitudes[peaks[0]] {
+        peaks.truncate(1);
+    }
+    peaks
+}
+
+/// Conventional name of the 12-TET interval nearest to the given size in
+/// cents (e.g. 702 cents is a perfect fifth). Returns None beyond an
+/// octave.
+pub fn interval_name(cents: f32) -> Option<&'static str> {
+    const NAMES: [&str; 13] = [
+        "unison",
+        "minor second",
+        "major second",
+        "minor third",
+        "major third",
+        "perfect fourth",
+        "tritone",
+        "perfect fifth",
+        "minor sixth",
+        "major sixth",
+        "minor seventh",
+        "major seventh",
+        "octave",
+    ];
+    let semitones = (cents.abs() / 100.0).round() as usize;
+    NAMES.get(semitones).copied()
+}
+
+/// Estimate the fundamental from the real cepstrum of a magnitude spectrum.
+///
+/// The log magnitudes are mirrored into a full symmetric spectrum, inverse
+/// transformed, and the strongest quefrency within a plausible pitch range
+/// (50 Hz to 1 kHz) is converted back to a frequency. Periodic harmonic
+/// structure shows up as a single cepstral peak, which makes this more
+/// robust than raw peak picking for voices and rich timbres.
+pub fn cepstrum_pitch(magnitudes: &[f32], sample_rate: usize, window_size: usize) -> Option<f32> {
+    if magnitudes.is_empty() || sample_rate == 0 || window_size == 0 {
+        return None;
+    }
+    let half = magnitudes.len().min(window_size / 2);
+    let mut log_spectrum = vec![Complex32::new(0.0, 0.0); window_size];
+    for (i, log_value) in magnitudes[..half]
+        .iter()
+        .map(|m| m.max(1e-10).ln())
+        .enumerate()
+    {
+        log_spectrum[i] = Complex32::new(log_value, 0.0);
+        if i > 0 {
+            log_spectrum[window_size - i] = Complex32::new(log_value, 0.0);
+        }
+    }
+    let mut planner = FftPlanner::<f32>::new();
+    planner.plan_fft_inverse(window_size).process(&mut log_spectrum);
+
+    let min_quefrency = (sample_rate as f32 / 1000.0) as usize;
+    let max_quefrency = ((sample_rate as f32 / 50.0) as usize).min(window_size / 2);
+    if min_quefrency >= max_quefrency {
+        return None;
+    }
+    let peak_quefrency = (min_quefrency..max_quefrency)
+        .max_by(|a, b| log_spectrum[*a].re.total_cmp(&log_spectrum[*b].re))?;
+    Some(sample_rate as f32 / peak_quefrency as f32)
+}
+
+/// Streaming linear-interpolation resampler.
+///
+/// Converts audio arriving in arbitrary chunks from one rate to another,
+/// carrying the fractional read position and the final sample across
+/// chunk boundaries so the output is seamless. Linear interpolation is
+/// plenty for pitch detection, where the frequencies of interest sit far
+/// below the Nyquist of either rate; it is not meant for hi-fi playback.
+pub struct StreamResampler {
+    from_rate: usize,
+    to_rate: usize,
+    /// Fractional position into the virtual stream `[last, input...]`.
+    position: f64,
+    /// Final sample of the previous chunk, for interpolation across the
+    /// chunk boundary.
+    last: f32,
+}
+
+impl StreamResampler {
+    pub fn new(from_rate: usize, to_rate: usize) -> Self {
+        StreamResampler {
+            from_rate,
+            to_rate,
+            position: 1.0,
+            last: 0.0,
+        }
+    }
+
+    /// Resample one chunk, returning however many output samples the input
+    /// advance allows.
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.from_rate == self.to_rate || self.from_rate == 0 || self.to_rate == 0 {
+            return input.to_vec();
+        }
+        if input.is_empty() {
+            return Vec::new();
+        }
+        let step = self.from_rate as f64 / self.to_rate as f64;
+        // Index 0 of the virtual stream is the carried-over last sample.
+        let virtual_len = input.len() + 1;
+        let mut output = Vec::with_capacity((input.len() as f64 / step) as usize + 1);
+        while self.position < (virtual_len - 1) as f64 {
+            let index = self.position as usize;
+            let frac = (self.position - index as f64) as f32;
+            let before = if index == 0 {
+                self.last
+            } else {
+                input[index - 1]
+            };
+            let after = input[index];
+            output.push(before + (after - before) * frac);
+            self.position += step;
+        }
+        self.last = input[input.len() - 1];
+        self.position -= (virtual_len - 1) as f64;
+        output
+    }
+}
+
+/// Resample a whole buffer from one rate to another in one call.
+pub fn resample(samples: &[f32], from_rate: usize, to_rate: usize) -> Vec<f32> {
+    StreamResampler::new(from_rate, to_rate).process(samples)
+}
+
+/// Cents equivalent of a multiplicative calibration factor, for showing
+/// the user how far their rig is being corrected.
+pub fn calibration_offset_cents(factor: f32) -> f32 {
+    if factor <= 0.0 {
+        return 0.0;
+    }
+    1200.0 * factor.log2()
+}
+
+/// Median smoothing over recent raw frequencies with change detection.
+///
+/// Plain median smoothing suppresses jitter but also averages across note
+/// changes, so a slide from one note to the next lags by the length of the
+/// window. When a new raw frequency lands further from the current
+/// smoothed value than the reset threshold, the window is flushed and the
+/// display snaps straight to the new note.
+pub struct PitchSmoother {
+    recent: Vec<f32>,
+    max_frames: usize,
+    reset_threshold_cents: f32,
+}
+
+impl PitchSmoother {
+    pub fn new(max_frames: usize, reset_threshold_cents: f32) -> Self {
+        PitchSmoother {
+            recent: Vec::new(),
+            max_frames: max_frames.max(1),
+            reset_threshold_cents,
+        }
+    }
+
+    /// Change the window length; older readings beyond it are dropped.
+    pub fn set_max_frames(&mut self, max_frames: usize) {
+        self.max_frames = max_frames.max(1);
+        if self.recent.len() > self.max_frames {
+            let excess = self.recent.len() - self.max_frames;
+            self.recent.drain(..excess);
+        }
+    }
+
+    /// Forget all recent readings, e.g. when the noise gate closes.
+    pub fn clear(&mut self) {
+        self.recent.clear();
+    }
+
+    /// Fold one raw reading into the window and return the smoothed value.
+    pub fn push(&mut self, freq: f32) -> f32 {
+        if !self.recent.is_empty() {
+            let smoothed = median(&self.recent);
+            if cents_offset(freq, smoothed).abs() > self.reset_threshold_cents {
+                self.recent.clear();
+            }
+        }
+        self.recent.push(freq);
+        if self.recent.len() > self.max_frames {
+            let excess = self.recent.len() - self.max_frames;
+            self.recent.drain(..excess);
+        }
+        median(&self.recent)
+    }
+}
+
+/// Estimate the fundamental from the average interval between rising zero
+/// crossings, avoiding the FFT entirely for low-CPU devices.
+///
+/// The buffer is first smoothed with a one-pole low-pass (cutoff around
+/// 1 kHz) so harmonics above the fundamental don't add extra crossings,
+/// and crossings only count once the signal has swung past a hysteresis
+/// band scaled to the peak level, so low-level noise around zero is
+/// ignored. Works for clean monophonic signals; rich or noisy material
+/// should use one of the spectral methods instead. Returns None when
+/// fewer than two clean crossings are found.
+pub fn zero_crossing_pitch(buffer: &[f32], sample_rate: usize) -> Option<f32> {
+    if buffer.is_empty() || sample_rate == 0 {
+        return None;
+    }
+    let cutoff = 1000.0f32;
+    let rc = 1.0 / (2.0 * PI * cutoff);
+    let dt = 1.0 / sample_rate as f32;
+    let alpha = dt / (rc + dt);
+    let mut filtered = Vec::with_capacity(buffer.len());
+    let mut state = 0.0f32;
+    for &sample in buffer {
+        state += alpha * (sample - state);
+        filtered.push(state);
+    }
+
+    let peak = filtered.iter().fold(0.0f32, |acc, v| acc.max(v.abs()));
+    if peak <= f32::EPSILON {
+        return None;
+    }
+    let hysteresis = peak * 0.1;
+
+    // Arm below the band, fire above it, so each cycle counts exactly once.
+    let mut armed = false;
+    let mut first_crossing: Option<usize> = None;
+    let mut last_crossing = 0usize;
+    let mut crossings = 0usize;
+    for (i, &value) in filtered.iter().enumerate() {
+        if value < -hysteresis {
+            armed = true;
+        } else if armed && value > hysteresis {
+            armed = false;
+            if first_crossing.is_none() {
+                first_crossing = Some(i);
+            }
+            last_crossing = i;
+            crossings += 1;
+        }
+    }
+    if crossings < 2 {
+        return None;
+    }
+    let average_period = (last_crossing - first_crossing?) as f32 / (crossings - 1) as f32;
+    Some(sample_rate as f32 / average_period)
+}
+
+/// Convert a frequency to its (possibly fractional) MIDI note number using
+/// the standard mapping of 440 Hz to note 69. Returns None for frequencies
+/// at or below zero, which have no logarithmic pitch.
+pub fn frequency_to_midi(freq: f32) -> Option<f32> {
+    if freq <= 0.0 {
+        return None;
+    }
+    Some(69.0 + 12.0 * (freq / 440.0).log2())
+}
+
+/// Detect the dominant frequency of a sample buffer by averaging STFT
+/// magnitude frames and picking the strongest bin. Returns None when the
+/// buffer is shorter than one analysis window.
+pub fn detect_pitch(
+    samples: &[f32],
+    sample_rate: usize,
+    window_size: usize,
+    hop_size: usize,
+) -> Option<f32> {
+    let stft_frames = compute_short_time_fourier_transform(samples, window_size, hop_size);
+    if stft_frames.is_empty() {
+        return None;
+    }
+    let num_bins = window_size / 2;
+    let mut average_magnitudes = vec![0.0f32; num_bins];
+    for frame in &stft_frames {
+        for (bin, value) in frame[..num_bins].iter().enumerate() {
+            average_magnitudes[bin] += value.norm();
+        }
+    }
+    for magnitude in &mut average_magnitudes {
+        *magnitude /= stft_frames.len() as f32;
+    }
+    let strongest_bin = average_magnitudes
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(bin, _)| bin)?;
+    let freq_resolution = sample_rate as f32 / window_size as f32;
+    Some(refine_peak_bin(&average_magnitudes, strongest_bin) * freq_resolution)
+}
+
+/// End-to-end pipeline check: synthesize a sine at the given frequency,
+/// run it through the standard detection path, and confirm the detected
+/// note is the one the frequency actually lies nearest. A failure points
+/// at the pipeline rather than the microphone, which is what makes it a
+/// useful diagnostic to run from the GUI with no instrument at hand.
+pub fn self_test(frequency: f32, sample_rate: usize) -> bool {
+    let Some((expected_note, _)) = frequency_to_note(frequency, Temperament::Equal, 0) else {
+        return false;
+    };
+    let samples: Vec<f32> = (0..4096 * 3)
+        .map(|i| (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin() * 0.5)
+        .collect();
+    match detect_note_from_samples(&samples, sample_rate) {
+        Some((note, _)) => note == expected_note,
+        None => false,
+    }
+}
+
+/// Zero out NaN or infinite samples in place and report whether any real
+/// signal remains. Some drivers emit NaNs on xruns, and a muted mic sends
+/// pure zeros; both would otherwise flow into the magnitude argmax (whose
+/// `partial_cmp` fallback can land on bin 0) and report a bogus note.
+/// Returns false when the window is silence, so callers can short-circuit
+/// to a "no signal" display.
+pub fn sanitize_window(buffer: &mut [f32]) -> bool {
+    let mut any_signal = false;
+    for sample in buffer.iter_mut() {
+        if !sample.is_finite() {
+            *sample = 0.0;
+        } else if *sample != 0.0 {
+            any_signal = true;
+        }
+    }
+    any_signal
+}
+
+/// Note detected from a raw sample slice: the live pipeline (sanitizing,
+/// STFT, averaged magnitudes, strongest bin, nearest equal-tempered note)
+/// as one pure function at the default 4096/2048 frame geometry, so
+/// detection behavior can be pinned down in tests without audio hardware.
+pub fn detect_note_from_samples(samples: &[f32], sample_rate: usize) -> Option<(String, f32)> {
+    let mut samples = samples.to_vec();
+    if !sanitize_window(&mut samples) {
+        return None;
+    }
+    let freq = detect_pitch(&samples, sample_rate, 4096, 2048)?;
+    frequency_to_note(freq, Temperament::Equal, 0)
+}
+
+/// Check that a buffer is long enough for one analysis window, returning
+/// an explicit message instead of a silent empty result when it is not.
+pub fn check_buffer_length(len: usize, window_size: usize) -> Result<(), String> {
+    if len < window_size {
+        Err(format!(
+            "audio too short for window size {} ({} samples available)",
+            window_size, len
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Approximate live-analysis latency in milliseconds for a frame geometry.
+///
+/// The pipeline must accumulate a full window before the first reading, and
+/// after that updates arrive one hop apart, so the expected delay between a
+/// sound and its reading is the window fill time plus one hop. Returns 0
+/// when the sample rate is 0.
+pub fn analysis_latency_ms(window_size: usize, hop_size: usize, sample_rate: usize) -> f32 {
+    if sample_rate == 0 {
+        return 0.0;
+    }
+    (window_size + hop_size) as f32 * 1000.0 / sample_rate as f32
+}
+
+/// One frame of a pitch-over-time analysis from [`analyze_pitch_track`].
+#[derive(Clone, Debug)]
+pub struct PitchFrame {
+    /// Frame start time in seconds from the beginning of the buffer.
+    pub time: f32,
+    /// Strongest detected frequency in Hz.
+    pub frequency: f32,
+    /// Nearest equal-tempered note label, None outside the note range.
+    pub note: Option<String>,
+    /// Offset from the note's target frequency in cents.
+    pub cents: f32,
+    /// Spectral clarity of the frame, higher meaning a more confident read.
+    pub confidence: f32,
+}
+
+/// Analyze a whole buffer into a per-frame pitch track: one windowed FFT
+/// per hop, each reduced to its strongest frequency, nearest note, cents
+/// offset, and a clarity score. This is the reusable engine behind custom
+/// UIs that want pitch over time rather than a single live reading.
+/// Returns an empty track when the buffer is shorter than one window.
+pub fn analyze_pitch_track(
+    samples: &[f32],
+    sample_rate: usize,
+    window_size: usize,
+    hop_size: usize,
+) -> Vec<PitchFrame> {
+    if window_size == 0 {
+        return Vec::new();
+    }
+    let bins = bin_frequencies(sample_rate, window_size);
+    compute_short_time_fourier_transform(samples, window_size, hop_size)
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let magnitudes: Vec<f32> = frame[..window_size / 2].iter().map(|v| v.norm()).collect();
+            let strongest = magnitudes
+                .iter()
+                .enumerate()
+                .skip(1)
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(bin, _)| bin)
+                .unwrap_or(0);
+            let frequency = bins.get(strongest).copied().unwrap_or(0.0);
+            let (note, cents) = match frequency_to_note(frequency, Temperament::Equal, 0) {
+                Some((note, target)) => (Some(note), cents_offset(frequency, target)),
+                None => (None, 0.0),
+            };
+            PitchFrame {
+                time: index as f32 * hop_size as f32 / sample_rate as f32,
+                frequency,
+                note,
+                cents,
+                confidence: spectral_clarity(&magnitudes),
+            }
+        })
+        .collect()
+}
+
+/// Find note onsets in an STFT as times in seconds.
+///
+/// The detector sums the positive per-bin magnitude differences between
+/// consecutive frames (spectral flux), which spikes when new energy
+/// appears and ignores energy decaying away. A frame counts as an onset
+/// when its flux is a local maximum and exceeds 1.5 times the moving
+/// average of the surrounding frames, so slow level drift doesn't trigger.
+pub fn detect_onsets(frames: &[Vec<Complex32>], sample_rate: usize, hop_size: usize) -> Vec<f32> {
+    if frames.len() < 3 || sample_rate == 0 {
+        return Vec::new();
+    }
+    let num_bins = frames[0].len() / 2;
+    let flux: Vec<f32> = frames
+        .windows(2)
+        .map(|pair| {
+            pair[0][..num_bins]
+                .iter()
+                .zip(&pair[1][..num_bins])
+                .map(|(prev, next)| (next.norm() - prev.norm()).max(0.0))
+                .sum()
+        })
+        .collect();
+    let average_window = 4usize;
+    // Hop-phase leakage jitter on a steady tone produces small flux peaks
+    // of its own, so besides beating the local moving average an onset
+    // must move a meaningful fraction of a typical frame's total energy.
+    let mean_energy = frames
+        .iter()
+        .map(|frame| frame[..num_bins].iter().map(|v| v.norm()).sum::<f32>())
+        .sum::<f32>()
+        / frames.len() as f32;
+    let energy_floor = 0.2 * mean_energy;
+    let mut onsets: Vec<f32> = Vec::new();
+    for i in 1..flux.len() - 1 {
+        let start = i.saturating_sub(average_window);
+        let end = (i + average_window + 1).min(flux.len());
+        let moving_average = flux[start..end].iter().sum::<f32>() / (end - start) as f32;
+        let is_peak = flux[i] > flux[i - 1] && flux[i] >= flux[i + 1];
+        if is_peak && flux[i] > 1.5 * moving_average && flux[i] > energy_floor {
+            // Flux index i compares frames i and i+1, so the onset lands
+            // in frame i+1. A window overlapping the attack can fire on
+            // consecutive hops; onsets within 50 ms merge into the first.
+            let time = (i + 1) as f32 * hop_size as f32 / sample_rate as f32;
+            if onsets.last().is_none_or(|last| time - last > 0.05) {
+                onsets.push(time);
+            }
+        }
+    }
+    onsets
+}
+
+/// Up to `max_notes` simultaneous fundamentals from a magnitude spectrum,
+/// strongest first.
+///
+/// Fundamentals are picked greedily: the strongest remaining local maximum
+/// is taken, then its harmonic series is masked out of the working copy so
+/// overtones of an already-found note can't register as notes of their
+/// own. Peaks below 5% of the strongest magnitude are considered noise and
+/// end the search. This is deliberately simple polyphony for chords of a
+/// few clearly sounding notes, not full transcription.
+pub fn detect_polyphonic_pitches(
+    magnitudes: &[f32],
+    sample_rate: usize,
+    window_size: usize,
+    max_notes: usize,
+) -> Vec<f32> {
+    if magnitudes.is_empty() || window_size == 0 {
+        return Vec::new();
+    }
+    let bin_width = sample_rate as f32 / window_size as f32;
+    let mut working = magnitudes.to_vec();
+    let noise_floor = 0.05 * magnitudes.iter().fold(0.0f32, |acc, v| acc.max(*v));
+    let mut fundamentals = Vec::new();
+    while fundamentals.len() < max_notes {
+        let Some(bin) = (1..working.len().saturating_sub(1))
+            .filter(|&i| working[i] > working[i - 1] && working[i] >= working[i + 1])
+            .max_by(|a, b| working[*a].total_cmp(&working[*b]))
+        else {
+            break;
+        };
+        if working[bin] <= noise_floor {
+            break;
+        }
+        let fundamental = bin as f32 * bin_width;
+        fundamentals.push(fundamental);
+        // Mask the fundamental and its overtones. The mask widens with the
+        // harmonic number because the fundamental is only known to half a
+        // bin, and that rounding error scales with the multiple.
+        for harmonic in 1..=8usize {
+            let center = (harmonic as f32 * fundamental / bin_width).round() as usize;
+            let half_width = 2 + harmonic;
+            for offset in center.saturating_sub(half_width)..=center + half_width {
+                if let Some(value) = working.get_mut(offset) {
+                    *value = 0.0;
+                }
+            }
+        }
+    }
+    fundamentals
+}
+
+/// Krumhansl-Schmuckler tone profiles: perceived stability of each pitch
+/// class relative to the tonic, from probe-tone experiments.
+static MAJOR_KEY_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+static MINOR_KEY_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Pearson correlation between two equal-length series.
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let mean_a = a.iter().sum::<f32>() / n;
+    let mean_b = b.iter().sum::<f32>() / n;
+    let mut covariance = 0.0;
+    let mut var_a = 0.0;
+    let mut var_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a <= 0.0 || var_b <= 0.0 {
+        return 0.0;
+    }
+    covariance / (var_a * var_b).sqrt()
+}
+
+/// Estimate the likely key of a pitch track as `(tonic, mode)`, e.g.
+/// `("C", "major")`.
+///
+/// A pitch-class histogram is accumulated over the detected frames and
+/// correlated against the Krumhansl-Schmuckler major and minor profiles in
+/// all twelve rotations; the best-correlated rotation wins. Returns None
+/// when no frame carries a usable pitch.
+pub fn estimate_key(pitch_track: &[PitchFrame]) -> Option<(String, String)> {
+    let mut histogram = [0.0f32; 12];
+    let mut counted = false;
+    for frame in pitch_track {
+        if let Some(midi) = frequency_to_midi(frame.frequency) {
+            let pitch_class = (midi.round() as i32).rem_euclid(12) as usize;
+            histogram[pitch_class] += 1.0;
+            counted = true;
+        }
+    }
+    if !counted {
+        return None;
+    }
+    let mut best: Option<(f32, usize, &str)> = None;
+    for tonic in 0..12 {
+        for (profile, mode) in [(&MAJOR_KEY_PROFILE, "major"), (&MINOR_KEY_PROFILE, "minor")] {
+            // Rotate the profile so its tonic entry lines up with `tonic`.
+            let rotated: Vec<f32> = (0..12).map(|i| profile[(i + 12 - tonic) % 12]).collect();
+            let score = correlation(&histogram, &rotated);
+            if best.is_none_or(|(best_score, _, _)| score > best_score) {
+                best = Some((score, tonic, mode));
+            }
+        }
+    }
+    best.map(|(_, tonic, mode)| (NOTES[tonic].0.to_string(), mode.to_string()))
+}
+
+/// Draw the averaged magnitude spectrum against bin center frequencies and
+/// save it as a PNG at the given path.
+pub fn plot_average_magnitudes_with_bins(
+    average_magnitudes: &[f32],
+    bin_centers: &[f32],
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(path, (900, 600)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let max_magnitude = average_magnitudes.iter().cloned().fold(f32::MIN, f32::max);
+
+    // Define x range from min to max bin center
+    let x_min = *bin_centers.first().unwrap_or(&0.0);
+    let x_max = *bin_centers.last().unwrap_or(&0.0);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            "Average Magnitudes per Frequency Bin",
+            ("sans-serif", 30).into_font(),
+        )
+        .margin(20)
+        .x_label_area_size(60)
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_min..x_max, 0f32..max_magnitude)?;
+
+    // Customize x axis to show bin centers with formatted labels
+    chart
+        .configure_mesh()
+        .x_desc("Frequency (Hz)")
+        .y_desc("Average Magnitude")
+        .x_labels(20)
+        .x_label_formatter(&|x| format!("{:.1}", x))
+        .draw()?;
+
+    // Connect points with line
+    chart.draw_series(LineSeries::new(
+        bin_centers
+            .iter()
+            .zip(average_magnitudes.iter())
+            .map(|(center, mag)| (*center, *mag)),
+        &BLUE,
+    ))?;
+
+    Ok(())
+}
+
+/// Center frequency of each FFT bin below the Nyquist, for labeling axes
+/// or mapping peak bins back to frequencies.
+pub fn bin_frequencies(sample_rate: usize, window_size: usize) -> Vec<f32> {
+    let bin_width = sample_rate as f32 / window_size as f32;
+    (0..window_size / 2).map(|i| i as f32 * bin_width).collect()
+}
+
+/// Lower and upper frequency bounds of each FFT bin below the Nyquist.
+pub fn compute_bin_ranges(sample_rate: usize, window_size: usize) -> Vec<(f32, f32)> {
+    let bin_width = sample_rate as f32 / window_size as f32;
+    bin_frequencies(sample_rate, window_size)
+        .iter()
+        .map(|center| (center - bin_width / 2.0, center + bin_width / 2.0))
+        .collect()
+}
+
+/// Windowed short-time Fourier transform over the buffer, one complex
+/// spectrum per hop.
+///
+/// A `zero_pad_factor` above 1 appends zeros after the windowed samples so a
+/// 4096 window with a factor of 4 yields a 16384-point FFT. The extra bins
+/// interpolate the spectrum for smoother display; they do not add true
+/// frequency resolution, which is fixed by the window length. A factor of 0
+/// is treated as 1.
+pub fn compute_short_time_fourier_transform_padded(
+    buffer: &[f32],
+    window_size: usize,
+    hop_size: usize,
+    zero_pad_factor: usize,
+) -> Vec<Vec<Complex32>> {
+    compute_short_time_fourier_transform_sized(
+        buffer,
+        window_size,
+        hop_size,
+        window_size * zero_pad_factor.max(1),
+    )
+}
+
+/// Hann window coefficients, `sin^2(pi * i / N)`: zero at the edges and
+/// one at the center. An earlier version used `sin^2(2 * pi * i / N)`,
+/// whose half-length period doubled the number of spectral lobes and
+/// smeared the peak search.
+pub fn hann_window(window_size: usize) -> Vec<f32> {
+    (0..window_size)
+        .map(|i| (PI * i as f32 / window_size as f32).sin().powi(2))
+        .collect()
+}
+
+/// Windowed short-time Fourier transform with an FFT length chosen
+/// independently of the window length.
+///
+/// The window length alone sets how much signal each frame spans, and with
+/// it both the latency and the true frequency resolution. An `fft_size`
+/// above the window pads with zeros, interpolating extra display bins; an
+/// `fft_size` below it truncates the windowed samples, trading resolution
+/// for a cheaper transform. Returns no frames when any parameter is zero.
+pub fn compute_short_time_fourier_transform_sized(
+    buffer: &[f32],
+    window_size: usize,
+    hop_size: usize,
+    fft_size: usize,
+) -> Vec<Vec<Complex32>> {
+    if window_size == 0 || hop_size == 0 || fft_size == 0 {
+        return Vec::new();
+    }
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(fft_size);
+    let hann = hann_window(window_size);
+    let mut spectrum = Vec::new();
+    let mut pos = 0;
+
+    while pos + window_size <= buffer.len() {
+        // Remove the window mean before the FFT so a DC offset from a
+        // miscalibrated interface doesn't leak into the low bins and win
+        // the peak search.
+        let segment = &buffer[pos..pos + window_size];
+        let mean = segment.iter().sum::<f32>() / window_size as f32;
+        let mut windowed: Vec<Complex32> = segment
+            .iter()
+            .zip(hann.iter())
+            .map(|(sample, w)| Complex32::new((sample - mean) * w, 0.0))
+            .collect();
+        // Pad up or truncate down to the transform length.
+        windowed.resize(fft_size, Complex32::new(0.0, 0.0));
+
+        fft.process(&mut windowed);
+        spectrum.push(windowed);
+        pos += hop_size;
+    }
+
+    spectrum
+}
+
+/// Windowed short-time Fourier transform without zero padding; see
+/// [`compute_short_time_fourier_transform_padded`].
+pub fn compute_short_time_fourier_transform(
+    buffer: &[f32],
+    window_size: usize,
+    hop_size: usize,
+) -> Vec<Vec<Complex32>> {
+    compute_short_time_fourier_transform_padded(buffer, window_size, hop_size, 1)
+}
+
+/// Reusable STFT state for repeated analysis of same-sized frames.
+///
+/// [`compute_short_time_fourier_transform`] plans the FFT and rebuilds the
+/// Hann window on every call, which is wasteful when the analysis thread runs
+/// every few milliseconds. This struct plans the FFT and computes the window
+/// coefficients once at construction and reuses the output frame storage
+/// across calls, so a steady-state `process` call only allocates when the
+/// buffer yields more hops than any previous call.
+pub struct StftProcessor {
+    fft: Arc<dyn Fft<f32>>,
+    window_size: usize,
+    hop_size: usize,
+    hann: Vec<f32>,
+    frames: Vec<Vec<Complex32>>,
+}
+
+impl StftProcessor {
+    /// Plan the forward FFT and Hann window for the given frame geometry.
+    pub fn new(window_size: usize, hop_size: usize) -> Self {
+        let mut planner = FftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(window_size);
+        let hann = hann_window(window_size);
+        StftProcessor {
+            fft,
+            window_size,
+            hop_size,
+            hann,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Compute the STFT of the buffer, one complex spectrum per hop.
+    ///
+    /// Output is identical to [`compute_short_time_fourier_transform`] with
+    /// the same window and hop sizes; the returned slice is only valid until
+    /// the next call.
+    pub fn process(&mut self, buffer: &[f32]) -> &[Vec<Complex32>] {
+        let mut num_frames = 0;
+        let mut pos = 0;
+
+        while pos + self.window_size <= buffer.len() {
+            if num_frames == self.frames.len() {
+                self.frames
+                    .push(vec![Complex32::new(0.0, 0.0); self.window_size]);
+            }
+            let frame = &mut self.frames[num_frames];
+            // Same DC removal as the one-shot transform, so the two paths
+            // keep producing identical frames.
+            let segment = &buffer[pos..pos + self.window_size];
+            let mean = segment.iter().sum::<f32>() / self.window_size as f32;
+            for ((out, sample), w) in frame.iter_mut().zip(segment).zip(&self.hann) {
+                *out = Complex32::new((sample - mean) * w, 0.0);
+            }
+
+            self.fft.process(frame);
+            num_frames += 1;
+            pos += self.hop_size;
+        }
+
+        &self.frames[..num_frames]
+    }
+
+    /// Compute the STFT and return one magnitude spectrum per hop, keeping
+    /// only the first half of each frame (the second half of a real
+    /// signal's FFT mirrors the first). Most callers only ever take
+    /// `.norm()` of the complex output, so this does that single pass here;
+    /// use [`StftProcessor::process`] when phase is needed.
+    pub fn magnitudes(&mut self, buffer: &[f32]) -> Vec<Vec<f32>> {
+        let num_bins = self.window_size / 2;
+        self.process(buffer)
+            .iter()
+            .map(|frame| frame[..num_bins].iter().map(|v| v.norm()).collect())
+            .collect()
+    }
+}
+
+/// Render STFT frames as a time-frequency heatmap PNG: time on the x axis,
+/// frequency on the y axis, and magnitude in dB relative to the loudest bin
+/// mapped to a color scale. Magnitudes are clamped to a -80 dB floor so
+/// near-silent bins share one background color instead of stretching the map.
+pub fn plot_spectrogram(
+    frames: &[Vec<Complex32>],
+    sample_rate: usize,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    if frames.is_empty() || frames[0].is_empty() {
+        return Err("no STFT frames to plot".into());
+    }
+    const DB_FLOOR: f32 = -80.0;
+
+    let num_bins = frames[0].len() / 2;
+    let magnitudes: Vec<Vec<f32>> = frames
+        .iter()
+        .map(|frame| frame[..num_bins].iter().map(|v| v.norm()).collect())
+        .collect();
+    let peak = magnitudes
+        .iter()
+        .flatten()
+        .copied()
+        .fold(f32::EPSILON, f32::max);
+
+    let root = BitMapBackend::new(path, (1024, 768)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let nyquist = sample_rate as f32 / 2.0;
+    let bin_width = nyquist / num_bins as f32;
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Spectrogram", ("sans-serif", 40))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0..frames.len(), 0.0_f32..nyquist)?;
+
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("Frame")
+        .y_desc("Frequency (Hz)")
+        .draw()?;
+
+    chart.draw_series(magnitudes.iter().enumerate().flat_map(|(frame_idx, frame)| {
+        frame.iter().enumerate().map(move |(bin, &magnitude)| {
+            let db = (20.0 * (magnitude / peak).max(f32::EPSILON).log10()).max(DB_FLOOR);
+            let intensity = (db - DB_FLOOR) / -DB_FLOOR;
+            // Dark blue through red as bins get louder.
+            let color = HSLColor(
+                (2.0 / 3.0) * (1.0 - intensity as f64),
+                1.0,
+                0.1 + 0.4 * intensity as f64,
+            );
+            Rectangle::new(
+                [
+                    (frame_idx, bin as f32 * bin_width),
+                    (frame_idx + 1, (bin + 1) as f32 * bin_width),
+                ],
+                color.filled(),
+            )
+        })
+    }))?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// Read an audio file of any supported format, returning its sample rate
+/// and mono f32 samples. WAV goes through [`read_wav`]; MP3, FLAC, and OGG
+/// are decoded with symphonia and multi-channel content is downmixed.
+pub fn read_audio(path: &str) -> Result<(usize, Vec<f32>), Box<dyn Error>> {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    if extension.as_deref() == Some("wav") {
+        return read_wav(path);
+    }
+
+    let file = std::fs::File::open(path)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = &extension {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &Default::default(),
+        &Default::default(),
+    )?;
+    let mut format = probed.format;
+    let track = format.default_track().ok_or("no audio track found")?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &Default::default())?;
+
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0) as usize;
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // Symphonia signals a normal end of stream as an unexpected EOF.
+            Err(SymphoniaError::IoError(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = decoder.decode(&packet)?;
+        let spec = *decoded.spec();
+        sample_rate = spec.rate as usize;
+        let channels = spec.channels.count();
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend(downmix_to_mono(buffer.samples(), channels));
+    }
+    if sample_rate == 0 || samples.is_empty() {
+        return Err(format!("'{}' contained no decodable audio", path).into());
+    }
+    Ok((sample_rate, samples))
+}
+
+/// Read a WAV file, returning its sample rate and normalized f32 samples.
+pub fn read_wav(path: &str) -> Result<(usize, Vec<f32>), Box<dyn Error>> {
+    let reader = WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader
+            .into_samples::<f32>()
+            .map(|s| s.map_err(|e| e.into()))
+            .collect::<Result<_, Box<dyn Error>>>()?,
+        SampleFormat::Int => {
+            let max_amplitude = 2_i32.pow(spec.bits_per_sample as u32 - 1) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| Ok(s? as f32 / max_amplitude))
+                .collect::<Result<_, Box<dyn Error>>>()?
+        }
+    };
+
+    Ok((spec.sample_rate as usize, samples))
+}
+
+/// Write mono f32 samples as a 32-bit float WAV file.
+pub fn write_wav(path: &str, samples: &[f32], sample_rate: usize) -> Result<(), Box<dyn Error>> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    let mut writer = WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample.clamp(-1.0, 1.0))?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Plot a magnitude spectrum against frequency and save it as a PNG.
+pub fn plot_spectrum(
+    freqs: &[f32],
+    magnitudes: &[f32],
+    filename: &str,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(filename, (1024, 768)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let max_y = magnitudes.iter().copied().fold(0.0_f32, f32::max);
+    let x_max = freqs.last().copied().unwrap_or(1.0);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Frequency Specturm", ("sans-serif", 40))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0.0_f32..x_max, 0.0_f32..max_y)?;
+
+    chart
+        .configure_mesh()
+        .x_desc("Frequency (Hz)")
+        .y_desc("Magnitude")
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(
+        freqs.iter().zip(magnitudes.iter()).map(|(&x, &y)| (x, y)),
+        &BLUE,
+    ))?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// Plot a time-domain waveform and save it as a PNG.
+pub fn plot_waveform(
+    samples: &[f32],
+    sample_rate: usize,
+    filename: &str,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(filename, (1024, 512)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    // Determine time range in seconds
+    let duration = samples.len() as f32 / sample_rate as f32;
+
+    // Find the min/max amplitude for Y-axis scaling
+    let (min_y, max_y) = samples
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), &val| {
+            (min.min(val), max.max(val))
+        });
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Audio Waveform", ("sans-serif", 30))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0f32..duration, min_y..max_y)?;
+
+    chart
+        .configure_mesh()
+        .x_desc("Time (s)")
+        .y_desc("Amplitude")
+        .x_labels(10)
+        .y_labels(5)
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, &y)| (i as f32 / sample_rate as f32, y)),
+        &BLUE,
+    ))?;
+
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn just_intonation_fifth_is_exact_three_halves() {
+        let frequencies = note_frequencies(Temperament::JustIntonation, 0);
+        // G is seven semitones above the C tonic.
+        let ratio = frequencies[7] / frequencies[0];
+        assert!((ratio - 1.5).abs() < 1e-6, "fifth ratio was {}", ratio);
+    }
+
+    #[test]
+    fn werckmeister_tempers_the_tonic_fifth_but_not_the_fourth() {
+        let ratios = Temperament::Werckmeister.ratios();
+        // The C-G fifth gives up a quarter of the Pythagorean comma,
+        // landing near 696.1 cents instead of the pure 702.
+        let fifth_cents = 1200.0 * ratios[7].log2();
+        assert!(
+            (fifth_cents - 696.1).abs() < 0.5,
+            "fifth was {} cents",
+            fifth_cents
+        );
+        // The fourth stays pure.
+        assert!((ratios[5] - 4.0 / 3.0).abs() < 1e-6);
+        // Every degree still lies within one octave in ascending order.
+        for pair in ratios.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+        assert!(ratios[11] < 2.0);
+    }
+
+    #[test]
+    fn read_audio_rejects_a_corrupt_file() {
+        let path = std::env::temp_dir().join("rustique_corrupt_test.mp3");
+        let path = path.to_str().unwrap().to_string();
+        std::fs::write(&path, b"this is not an mp3 stream at all").unwrap();
+        assert!(read_audio(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_audio_falls_back_to_wav() {
+        let sample_rate = 44100;
+        let samples: Vec<f32> = (0..2048)
+            .map(|i| (PI * 2.0 * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+        let path = std::env::temp_dir().join("rustique_read_audio_test.wav");
+        let path = path.to_str().unwrap().to_string();
+        write_wav(&path, &samples, sample_rate).unwrap();
+        let (rate, decoded) = read_audio(&path).unwrap();
+        assert_eq!(rate, sample_rate);
+        assert_eq!(decoded.len(), samples.len());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn median_aggregation_ignores_a_transient_frame() {
+        let mut steady = vec![0.0f32; 8];
+        steady[5] = 1.0;
+        let mut transient = vec![0.0f32; 8];
+        transient[2] = 9.0;
+        let frames = vec![steady.clone(), steady, transient];
+
+        let strongest = |magnitudes: &[f32]| {
+            magnitudes
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(bin, _)| bin)
+                .unwrap()
+        };
+        let mean = aggregate_magnitudes(&frames, FrameAggregation::Mean);
+        let median = aggregate_magnitudes(&frames, FrameAggregation::Median);
+        assert_eq!(strongest(&mean), 2, "mean is dominated by the transient");
+        assert_eq!(strongest(&median), 5, "median keeps the steady tone");
+    }
+
+    #[test]
+    fn out_of_range_frequencies_return_none() {
+        assert!(frequency_to_note(20_000.0, Temperament::Equal, 0).is_none());
+        assert!(frequency_to_note(4.0, Temperament::Equal, 0).is_none());
+    }
+
+    #[test]
+    fn low_sub_bass_maps_into_the_bottom_octave() {
+        let (note, target) = frequency_to_note(30.0, Temperament::Equal, 0).unwrap();
+        assert_eq!(note, "B0");
+        assert!((target - 30.87).abs() < 0.1, "target was {}", target);
+    }
+
+    #[test]
+    fn octave_numbers_roll_over_at_c() {
+        let note = |freq: f32| frequency_to_note(freq, Temperament::Equal, 0).unwrap().0;
+        assert_eq!(note(261.63), "C4");
+        assert_eq!(note(493.88), "B4");
+        assert_eq!(note(523.25), "C5");
+    }
+
+    #[test]
+    fn a4_reference_rescales_targets_without_renaming_notes() {
+        // At baroque pitch 415 Hz lands dead on A4 rather than a flat G#4.
+        let (note, target) = frequency_to_note_at(415.0, Temperament::Equal, 0, 415.0).unwrap();
+        assert_eq!(note, "A4");
+        assert!((target - 415.0).abs() < 0.01, "target was {}", target);
+        assert!(cents_offset(415.0, target).abs() < 0.1);
+        // The rescaled table agrees with the wrapper across the range.
+        let table = NoteTable::with_a4(Temperament::Equal, 0, 442.0);
+        for freq in [98.0, 221.0, 442.0, 884.0] {
+            assert_eq!(
+                table.lookup(freq),
+                frequency_to_note_at(freq, Temperament::Equal, 0, 442.0)
+            );
+        }
+        // A4 of 440 is the identity.
+        assert_eq!(
+            apply_a4_reference(note_frequencies(Temperament::Equal, 0), 440.0),
+            note_frequencies(Temperament::Equal, 0)
+        );
+    }
+
+    #[test]
+    fn a_weighting_is_flat_at_one_kilohertz() {
+        assert!(a_weight(1000.0).abs() < 0.2, "got {}", a_weight(1000.0));
+        // The curve rolls off steeply at the low end.
+        assert!(a_weight(50.0) < -25.0);
+    }
+
+    #[test]
+    fn peaks_at_440_and_660_form_a_perfect_fifth() {
+        let sample_rate = 44100;
+        let window_size = 4096;
+        let bin_width = sample_rate as f32 / window_size as f32;
+        let mut magnitudes = vec![0.01f32; window_size / 2];
+        let low_bin = (440.0 / bin_width).round() as usize;
+        let high_bin = (660.0 / bin_width).round() as usize;
+        magnitudes[low_bin] = 1.0;
+        magnitudes[high_bin] = 0.8;
+        let peaks = top_two_peaks(&magnitudes);
+        assert_eq!(peaks, vec![low_bin, high_bin]);
+        assert_eq!(interval_name(cents_offset(660.0, 440.0)), Some("perfect fifth"));
+    }
+
+    #[test]
+    fn quiet_second_peak_is_not_reported() {
+        let mut magnitudes = vec![0.0f32; 64];
+        magnitudes[10] = 1.0;
+        magnitudes[30] = 0.05;
+        assert_eq!(top_two_peaks(&magnitudes), vec![10]);
+    }
+
+    #[test]
+    fn band_limit_discards_out_of_band_peak() {
+        let sample_rate = 44100;
+        let window_size = 4096;
+        let bin_width = sample_rate as f32 / window_size as f32;
+        let mut magnitudes = vec![0.0f32; window_size / 2];
+        let rumble_bin = (30.0 / bin_width).round() as usize;
+        let tone_bin = (440.0 / bin_width).round() as usize;
+        magnitudes[rumble_bin] = 2.0;
+        magnitudes[tone_bin] = 1.0;
+        band_limit(&mut magnitudes, sample_rate, window_size, 40.0, 2000.0);
+        let strongest = magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(bin, _)| bin)
+            .unwrap();
+        assert_eq!(strongest, tone_bin);
+        assert_eq!(magnitudes[rumble_bin], 0.0);
+    }
+
+    #[test]
+    fn cepstrum_recovers_fundamental_of_harmonic_spectrum() {
+        let sample_rate = 44100;
+        let window_size = 4096;
+        let fundamental = 200.0f32;
+        let bin_width = sample_rate as f32 / window_size as f32;
+        let mut magnitudes = vec![0.05f32; window_size / 2];
+        for harmonic in 1..=10 {
+            let bin = (harmonic as f32 * fundamental / bin_width).round() as usize;
+            magnitudes[bin] = 1.0;
+        }
+        let freq = cepstrum_pitch(&magnitudes, sample_rate, window_size).unwrap();
+        assert!(
+            (freq - fundamental).abs() < 5.0,
+            "recovered {} Hz for a {} Hz fundamental",
+            freq,
+            fundamental
+        );
+    }
+
+    #[test]
+    fn dc_offset_does_not_win_the_peak_search() {
+        let sample_rate = 44100;
+        let window_size = 4096;
+        // A large offset with a comparatively quiet real tone on top.
+        let samples: Vec<f32> = (0..window_size)
+            .map(|i| 0.8 + (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin() * 0.1)
+            .collect();
+        let frames = compute_short_time_fourier_transform(&samples, window_size, window_size);
+        let magnitudes: Vec<f32> = frames[0][..window_size / 2].iter().map(|v| v.norm()).collect();
+        let strongest = magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(bin, _)| bin)
+            .unwrap();
+        let expected = (440.0 * window_size as f32 / sample_rate as f32).round() as usize;
+        assert!(
+            (strongest as i32 - expected as i32).abs() <= 1,
+            "strongest bin was {}, expected about {}",
+            strongest,
+            expected
+        );
+    }
+
+    #[test]
+    fn notch_removes_a_mains_hum_bin() {
+        let sample_rate = 44100;
+        let window_size = 4096;
+        let bin_width = sample_rate as f32 / window_size as f32;
+        let mut magnitudes = vec![0.0f32; window_size / 2];
+        let hum_bin = (50.0 / bin_width).round() as usize;
+        magnitudes[hum_bin] = 1.0;
+        notch_out(&mut magnitudes, sample_rate, window_size, 50.0, bin_width);
+        assert_eq!(magnitudes[hum_bin], 0.0);
+    }
+
+    #[test]
+    fn bin_frequencies_span_zero_to_nyquist() {
+        let bins = bin_frequencies(44100, 4096);
+        assert_eq!(bins.len(), 2048);
+        assert_eq!(bins[0], 0.0);
+        let nyquist = 44100.0 / 2.0;
+        let bin_width = 44100.0 / 4096.0;
+        assert!(
+            (nyquist - bins[2047]) <= bin_width,
+            "last bin was {} Hz",
+            bins[2047]
+        );
+    }
+
+    #[test]
+    fn frequency_resolution_follows_the_sample_rate() {
+        // The same window size yields a different bin width at a different
+        // rate, so the rate must be read from the live stream, not assumed.
+        assert_eq!(bin_frequencies(44100, 4096)[1], 44100.0 / 4096.0);
+        assert_eq!(bin_frequencies(48000, 4096)[1], 48000.0 / 4096.0);
+    }
+
+    #[test]
+    fn hann_window_is_zero_at_the_edges_and_one_at_the_center() {
+        let window = hann_window(4096);
+        assert_eq!(window.len(), 4096);
+        assert!(window[0].abs() < 1e-6, "first coefficient was {}", window[0]);
+        assert!(
+            window[4095].abs() < 1e-5,
+            "last coefficient was {}",
+            window[4095]
+        );
+        assert!(
+            (window[2048] - 1.0).abs() < 1e-6,
+            "center coefficient was {}",
+            window[2048]
+        );
+        // A single main lobe: coefficients rise monotonically to the center.
+        for pair in window[..2048].windows(2) {
+            assert!(pair[1] >= pair[0]);
+        }
+    }
+
+    #[test]
+    fn sized_transform_pads_a_short_window_up() {
+        let samples: Vec<f32> = (0..4096)
+            .map(|i| (2.0 * PI * 440.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let frames = compute_short_time_fourier_transform_sized(&samples, 4096, 4096, 8192);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].len(), 8192);
+    }
+
+    #[test]
+    fn sized_transform_truncates_the_window_down() {
+        let samples: Vec<f32> = (0..4096)
+            .map(|i| (2.0 * PI * 440.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let frames = compute_short_time_fourier_transform_sized(&samples, 4096, 4096, 1024);
+        assert_eq!(frames[0].len(), 1024);
+        let magnitudes: Vec<f32> = frames[0][..512].iter().map(|v| v.norm()).collect();
+        let strongest = magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(bin, _)| bin)
+            .unwrap();
+        let expected = (440.0f32 * 1024.0 / 44100.0).round() as usize;
+        assert!(
+            (strongest as i32 - expected as i32).abs() <= 2,
+            "strongest bin {} for expected {}",
+            strongest,
+            expected
+        );
+    }
+
+    #[test]
+    fn short_buffers_get_an_explicit_message() {
+        let err = check_buffer_length(1000, 4096).unwrap_err();
+        assert_eq!(err, "audio too short for window size 4096 (1000 samples available)");
+        assert!(check_buffer_length(4096, 4096).is_ok());
+    }
+
+    #[test]
+    fn latency_tracks_the_frame_geometry() {
+        // 4096 + 2048 samples at 44.1 kHz is just under 140 ms.
+        let full = analysis_latency_ms(4096, 2048, 44100);
+        assert!((full - 139.3).abs() < 0.1, "got {} ms", full);
+        // A quarter-size window cuts the latency by the same factor.
+        let low = analysis_latency_ms(1024, 512, 44100);
+        assert!((low - full / 4.0).abs() < 0.01);
+        assert_eq!(analysis_latency_ms(4096, 2048, 0), 0.0);
+    }
+
+    #[test]
+    fn one_onset_is_found_between_two_tones() {
+        let sample_rate = 44100;
+        let seconds_per_tone = 1.0;
+        let tone_len = (sample_rate as f32 * seconds_per_tone) as usize;
+        let mut samples: Vec<f32> = (0..tone_len)
+            .map(|i| (2.0 * PI * 220.0 * i as f32 / sample_rate as f32).sin() * 0.4)
+            .collect();
+        samples.extend(
+            (0..tone_len).map(|i| (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin() * 0.4),
+        );
+        let frames = compute_short_time_fourier_transform(&samples, 2048, 512);
+        let onsets = detect_onsets(&frames, sample_rate, 512);
+        assert_eq!(onsets.len(), 1, "onsets at {:?}", onsets);
+        assert!(
+            (onsets[0] - seconds_per_tone).abs() < 0.1,
+            "onset at {} s",
+            onsets[0]
+        );
+    }
+
+    #[test]
+    fn steady_tone_has_no_onsets() {
+        let sample_rate = 44100;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|i| (2.0 * PI * 220.0 * i as f32 / sample_rate as f32).sin() * 0.4)
+            .collect();
+        let frames = compute_short_time_fourier_transform(&samples, 2048, 512);
+        assert!(detect_onsets(&frames, sample_rate, 512).is_empty());
+    }
+
+    #[test]
+    fn polyphonic_detection_finds_a_c_major_triad() {
+        let sample_rate = 44100;
+        let window_size = 4096;
+        let bin_width = sample_rate as f32 / window_size as f32;
+        let mut magnitudes = vec![0.01f32; window_size / 2];
+        // C4, E4, G4 fundamentals with a couple of overtones each.
+        for &fundamental in &[261.63f32, 329.63, 392.00] {
+            for harmonic in 1..=3 {
+                let bin = (harmonic as f32 * fundamental / bin_width).round() as usize;
+                magnitudes[bin] = 1.0 / harmonic as f32;
+            }
+        }
+        let notes: Vec<String> =
+            detect_polyphonic_pitches(&magnitudes, sample_rate, window_size, 4)
+                .iter()
+                .filter_map(|&freq| {
+                    frequency_to_note(freq, Temperament::Equal, 0).map(|(note, _)| note)
+                })
+                .collect();
+        for expected in ["C4", "E4", "G4"] {
+            assert!(
+                notes.iter().any(|note| note == expected),
+                "{} missing from {:?}",
+                expected,
+                notes
+            );
+        }
+    }
+
+    #[test]
+    fn overtones_are_not_reported_as_extra_notes() {
+        let sample_rate = 44100;
+        let window_size = 4096;
+        let bin_width = sample_rate as f32 / window_size as f32;
+        let mut magnitudes = vec![0.01f32; window_size / 2];
+        for harmonic in 1..=6 {
+            let bin = (harmonic as f32 * 220.0 / bin_width).round() as usize;
+            magnitudes[bin] = 1.0 / harmonic as f32;
+        }
+        let pitches = detect_polyphonic_pitches(&magnitudes, sample_rate, window_size, 4);
+        assert_eq!(pitches.len(), 1, "detected {:?}", pitches);
+        assert!((pitches[0] - 220.0).abs() < bin_width);
+    }
+
+    #[test]
+    fn c_major_scale_is_estimated_as_c_major() {
+        let scale = [
+            261.63, 293.66, 329.63, 349.23, 392.00, 440.00, 493.88, 523.25,
+        ];
+        let track: Vec<PitchFrame> = scale
+            .iter()
+            .enumerate()
+            .map(|(i, &frequency)| PitchFrame {
+                time: i as f32 * 0.5,
+                frequency,
+                note: None,
+                cents: 0.0,
+                confidence: 10.0,
+            })
+            .collect();
+        let (tonic, mode) = estimate_key(&track).unwrap();
+        assert_eq!(tonic, "C");
+        assert_eq!(mode, "major");
+    }
+
+    #[test]
+    fn key_of_an_empty_track_is_unknown() {
+        assert!(estimate_key(&[]).is_none());
+    }
+
+    #[test]
+    fn calibration_factor_shifts_pitch_by_the_expected_cents() {
+        let calibrated = 440.0 * 1.01;
+        let shift = cents_offset(calibrated, 440.0);
+        assert!(
+            (shift - calibration_offset_cents(1.01)).abs() < 0.01,
+            "shift {} vs factor offset {}",
+            shift,
+            calibration_offset_cents(1.01)
+        );
+        // 1% in frequency is about 17.2 cents.
+        assert!((shift - 17.2).abs() < 0.2, "shift was {}", shift);
+        assert_eq!(calibration_offset_cents(1.0), 0.0);
+    }
+
+    #[test]
+    fn resampling_preserves_a_sine_frequency() {
+        let from_rate = 48000;
+        let to_rate = 44100;
+        let samples: Vec<f32> = (0..from_rate)
+            .map(|i| (2.0 * PI * 440.0 * i as f32 / from_rate as f32).sin() * 0.5)
+            .collect();
+        let resampled = resample(&samples, from_rate, to_rate);
+        let expected_len = to_rate as f32;
+        assert!(
+            (resampled.len() as f32 - expected_len).abs() < 4.0,
+            "one second became {} samples",
+            resampled.len()
+        );
+        let freq = detect_pitch(&resampled, to_rate, 4096, 2048).unwrap();
+        let bin_width = to_rate as f32 / 4096.0;
+        assert!(
+            (freq - 440.0).abs() <= bin_width,
+            "detected {} Hz after resampling",
+            freq
+        );
+    }
+
+    #[test]
+    fn chunked_resampling_matches_one_shot() {
+        let samples: Vec<f32> = (0..4800)
+            .map(|i| (2.0 * PI * 220.0 * i as f32 / 48000.0).sin())
+            .collect();
+        let one_shot = resample(&samples, 48000, 44100);
+        let mut streaming = StreamResampler::new(48000, 44100);
+        let mut chunked = Vec::new();
+        for chunk in samples.chunks(441) {
+            chunked.extend(streaming.process(chunk));
+        }
+        assert_eq!(one_shot.len(), chunked.len());
+        for (a, b) in one_shot.iter().zip(&chunked) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn smoother_snaps_to_a_step_change_in_pitch() {
+        let mut smoother = PitchSmoother::new(5, 100.0);
+        for _ in 0..5 {
+            smoother.push(220.0);
+        }
+        // A jump of an octave flushes the window, so the very next reading
+        // wins instead of being outvoted by the old note.
+        assert_eq!(smoother.push(440.0), 440.0);
+    }
+
+    #[test]
+    fn smoother_still_suppresses_small_jitter() {
+        let mut smoother = PitchSmoother::new(5, 100.0);
+        smoother.push(220.0);
+        smoother.push(221.0);
+        let smoothed = smoother.push(219.5);
+        assert!(
+            (smoothed - 220.0).abs() < 1.0,
+            "smoothed to {} Hz",
+            smoothed
+        );
+    }
+
+    #[test]
+    fn zero_crossings_recover_a_pure_220_hz_sine() {
+        let sample_rate = 44100;
+        let samples: Vec<f32> = (0..4096)
+            .map(|i| (2.0 * PI * 220.0 * i as f32 / sample_rate as f32).sin() * 0.5)
+            .collect();
+        let freq = zero_crossing_pitch(&samples, sample_rate).unwrap();
+        assert!(
+            (freq - 220.0).abs() < 2.0,
+            "estimated {} Hz for a 220 Hz sine",
+            freq
+        );
+    }
+
+    #[test]
+    fn zero_crossings_reject_silence() {
+        assert!(zero_crossing_pitch(&vec![0.0; 4096], 44100).is_none());
+    }
+
+    #[test]
+    fn quarter_tone_between_a4_and_a_sharp_is_labeled_half_sharp() {
+        // One 24-EDO step above A4.
+        let freq = 440.0 * 2f32.powf(1.0 / 24.0);
+        let (label, target) = frequency_to_edo_note(freq, 24).unwrap();
+        assert_eq!(label, "A4 half-sharp");
+        assert!((target - freq).abs() < 0.5, "target was {}", target);
+    }
+
+    #[test]
+    fn note_table_lookup_matches_the_linear_search_over_a_sweep() {
+        for temperament in Temperament::ALL {
+            let table = NoteTable::new(temperament, 0);
+            // Log sweep through the whole supported range.
+            for step in 0..400 {
+                let freq = 20.0f32 * 1.016f32.powi(step);
+                assert_eq!(
+                    table.lookup(freq),
+                    frequency_to_note(freq, temperament, 0),
+                    "diverged at {} Hz under {}",
+                    freq,
+                    temperament.name()
+                );
+            }
+        }
+        assert!(NoteTable::new(Temperament::Equal, 0).lookup(0.0).is_none());
+    }
+
+    #[test]
+    fn flat_spelling_reports_b_flat_instead_of_a_sharp() {
+        let (label, _) = frequency_to_note(466.16, Temperament::Equal, 0).unwrap();
+        assert_eq!(label, "A#4");
+        assert_eq!(spell_note_label(&label, NoteSpelling::Flats), "B♭4");
+        assert_eq!(spell_note_label(&label, NoteSpelling::UnicodeSharps), "A♯4");
+        assert_eq!(spell_note_label(&label, NoteSpelling::Sharps), "A#4");
+        // Naturals pass through in every style.
+        assert_eq!(spell_note_label("A4", NoteSpelling::Flats), "A4");
+    }
+
+    #[test]
+    fn transposition_composes_with_spelling() {
+        // The raw ASCII label is transposed first and respelled second;
+        // the other order would hand transpose_note_label a label like
+        // "B♭3" that matches nothing in NOTES.
+        let shown = |label: &str, semitones: i32, spelling: NoteSpelling| {
+            spell_note_label(&transpose_note_label(label, semitones), spelling)
+        };
+        assert_eq!(shown("A#3", 2, NoteSpelling::Flats), "C4");
+        assert_eq!(shown("G#4", -2, NoteSpelling::Flats), "G♭4");
+        assert_eq!(shown("A4", 1, NoteSpelling::UnicodeSharps), "A♯4");
+    }
+
+    #[test]
+    fn twelve_edo_matches_plain_note_labels() {
+        let (label, target) = frequency_to_edo_note(440.0, 12).unwrap();
+        assert_eq!(label, "A4");
+        assert!((target - 440.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn midi_numbers_match_standard_tuning() {
+        assert!((frequency_to_midi(440.0).unwrap() - 69.0).abs() < 1e-4);
+        assert!((frequency_to_midi(261.63).unwrap() - 60.0).abs() < 0.01);
+        assert!(frequency_to_midi(0.0).is_none());
+        assert!(frequency_to_midi(-5.0).is_none());
+    }
+
+    #[test]
+    fn zero_padding_multiplies_fft_length() {
+        let samples = vec![0.5f32; 1024];
+        let frames = compute_short_time_fourier_transform_padded(&samples, 512, 256, 4);
+        assert!(!frames.is_empty());
+        for frame in &frames {
+            assert_eq!(frame.len(), 512 * 4);
+        }
+    }
+
+    #[test]
+    fn stft_processor_matches_one_shot_transform() {
+        let samples: Vec<f32> = (0..2048)
+            .map(|i| (PI * 2.0 * 440.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let expected = compute_short_time_fourier_transform(&samples, 512, 256);
+        let mut processor = StftProcessor::new(512, 256);
+        // Run twice to exercise frame reuse across calls.
+        processor.process(&samples);
+        let frames = processor.process(&samples);
+        assert_eq!(frames.len(), expected.len());
+        for (frame, expected_frame) in frames.iter().zip(&expected) {
+            for (bin, expected_bin) in frame.iter().zip(expected_frame) {
+                assert!((bin - expected_bin).norm() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn narrow_peak_beats_a_broad_noise_bump() {
+        // A strong narrow partial at bin 50 and a broad jittery noise
+        // hump around bin 150: the hump's jagged local maxima rise only
+        // slightly above their neighbors, so only the partial (and the
+        // hump's single apex) survive the prominence filter, partial
+        // first.
+        let mut magnitudes: Vec<f32> = (0..256)
+            .map(|i| {
+                let offset = (i as f32 - 150.0) / 40.0;
+                0.5 * (-offset * offset).exp() + 0.03 * (i % 2) as f32
+            })
+            .collect();
+        magnitudes[49] = 0.2;
+        magnitudes[50] = 1.0;
+        magnitudes[51] = 0.2;
+        let peaks = find_peaks(&magnitudes, 0.3, 5);
+        assert_eq!(peaks.first(), Some(&50));
+        assert!(peaks.len() <= 2, "jitter maxima got through: {:?}", peaks);
+        // Two tall bins closer than the spacing threshold collapse to the
+        // more prominent one.
+        let mut pair = vec![0.0f32; 32];
+        pair[10] = 1.0;
+        pair[12] = 0.9;
+        assert_eq!(find_peaks(&pair, 0.3, 5), vec![10]);
+        assert!(find_peaks(&[], 0.3, 5).is_empty());
+    }
+
+    #[test]
+    fn inharmonicity_is_positive_for_stretched_partials() {
+        let freq_resolution = 44100.0 / 4096.0;
+        // Fundamental on an exact bin so the harmonic control case has no
+        // quantization error.
+        let fundamental = 20.0 * freq_resolution;
+        let b_true = 0.0008f32;
+        let mut stretched = vec![0.0f32; 2048];
+        let mut harmonic = vec![0.0f32; 2048];
+        for n in 1..=8usize {
+            let stretch = (1.0 + b_true * (n * n) as f32).sqrt();
+            let amplitude = 1.0 / n as f32;
+            stretched[(n as f32 * fundamental * stretch / freq_resolution).round() as usize] =
+                amplitude;
+            harmonic[(n as f32 * fundamental / freq_resolution).round() as usize] = amplitude;
+        }
+        let b = estimate_inharmonicity(&stretched, fundamental, freq_resolution).unwrap();
+        assert!(b > 0.0, "B = {}", b);
+        assert!((b - b_true).abs() < 0.0003, "B = {} vs true {}", b, b_true);
+        let b_harmonic = estimate_inharmonicity(&harmonic, fundamental, freq_resolution).unwrap();
+        assert!(b_harmonic.abs() < 1e-6, "harmonic B = {}", b_harmonic);
+        // Silence has no partials to fit.
+        assert!(estimate_inharmonicity(&vec![0.0; 2048], fundamental, freq_resolution).is_none());
+    }
+
+    #[test]
+    fn pre_emphasis_boosts_high_frequencies_over_low() {
+        let sample_rate = 44100;
+        let energy_ratio = |frequency: f32| {
+            let tone: Vec<f32> = (0..4096)
+                .map(|i| (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin())
+                .collect();
+            let filtered = pre_emphasis(&tone, 0.95);
+            let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+            energy(&filtered) / energy(&tone)
+        };
+        // The response rises with frequency: a low tone is attenuated, a
+        // high one comes through near full strength.
+        let low = energy_ratio(110.0);
+        let high = energy_ratio(4000.0);
+        assert!(high > 4.0 * low, "low ratio {}, high ratio {}", low, high);
+        assert!(low < 0.1, "110 Hz should be strongly attenuated: {}", low);
+        // A zero coefficient is a pass-through.
+        let tone = vec![0.25, -0.5, 0.75];
+        assert_eq!(pre_emphasis(&tone, 0.0), tone);
+    }
+
+    #[test]
+    fn spectrum_smoothing_converges_to_a_steady_input() {
+        let target = vec![1.0f32, 0.5, 0.25];
+        let mut smoothed = vec![0.0f32; 3];
+        for _ in 0..40 {
+            smooth_spectrum(&mut smoothed, &target, 0.5);
+        }
+        for (value, expected) in smoothed.iter().zip(&target) {
+            assert!((value - expected).abs() < 1e-5, "{} vs {}", value, expected);
+        }
+        // A size change resets instead of blending mismatched bins.
+        smooth_spectrum(&mut smoothed, &[2.0, 2.0], 0.5);
+        assert_eq!(smoothed, vec![2.0, 2.0]);
+    }
+
+    #[test]
+    fn self_test_passes_on_a_healthy_pipeline() {
+        assert!(self_test(440.0, 44100));
+        // Outside the note range there is nothing to verify against.
+        assert!(!self_test(0.0, 44100));
+    }
+
+    #[test]
+    fn silence_and_nan_windows_report_no_note() {
+        // A muted mic delivers pure zeros: no note, not a bin-0 label.
+        assert!(detect_note_from_samples(&vec![0.0; 4096 * 3], 44100).is_none());
+        // A NaN from a driver xrun is zeroed out instead of poisoning the
+        // spectrum; the surrounding tone still wins.
+        let mut samples: Vec<f32> = (0..4096 * 3)
+            .map(|i| (2.0 * PI * 440.0 * i as f32 / 44100.0).sin() * 0.5)
+            .collect();
+        samples[1234] = f32::NAN;
+        samples[2345] = f32::INFINITY;
+        let (note, _) = detect_note_from_samples(&samples, 44100).unwrap();
+        assert_eq!(note, "A4");
+        // All-NaN comes out as silence.
+        let mut nans = vec![f32::NAN; 4096];
+        assert!(!sanitize_window(&mut nans));
+        assert_eq!(nans, vec![0.0; 4096]);
+    }
+
+    #[test]
+    fn drift_stats_match_a_known_sequence() {
+        let mut stats = DriftStats::new();
+        for cents in [2.0, -2.0, 10.0, -10.0, 4.0] {
+            stats.push(cents);
+        }
+        assert_eq!(stats.count(), 5);
+        assert!((stats.mean() - 0.8).abs() < 1e-4, "mean was {}", stats.mean());
+        // Sample standard deviation of the sequence is sqrt(55.2).
+        assert!(
+            (stats.std_dev() - 55.2f32.sqrt()).abs() < 1e-3,
+            "std dev was {}",
+            stats.std_dev()
+        );
+        assert!((stats.in_tune_percent() - 60.0).abs() < 1e-4);
+        stats.clear();
+        assert_eq!(stats.count(), 0);
+        assert_eq!(stats.std_dev(), 0.0);
+        assert_eq!(stats.in_tune_percent(), 0.0);
+    }
+
+    #[test]
+    fn notes_are_recovered_from_generated_sines() {
+        let sample_rate = 44100;
+        for (freq, expected) in [(261.63, "C4"), (440.0, "A4"), (659.25, "E5"), (880.0, "A5")] {
+            let samples: Vec<f32> = (0..4096 * 3)
+                .map(|i| (2.0 * PI * freq * i as f32 / sample_rate as f32).sin() * 0.5)
+                .collect();
+            let (note, target) = detect_note_from_samples(&samples, sample_rate).unwrap();
+            assert_eq!(note, expected, "misnamed a {} Hz tone", freq);
+            assert!((target - freq).abs() < target * 0.03);
+        }
+        assert!(detect_note_from_samples(&[0.0; 1000], sample_rate).is_none());
+    }
+
+    #[test]
+    fn whitening_evens_out_a_steep_roll_off() {
+        // 1/(1+i) envelope with two peaks of equal prominence relative to
+        // their surroundings; the raw spectrum makes the low one dominate.
+        let mut magnitudes: Vec<f32> = (0..512).map(|i| 1.0 / (1.0 + i as f32)).collect();
+        magnitudes[10] *= 4.0;
+        magnitudes[400] *= 4.0;
+        let raw_ratio = magnitudes[10] / magnitudes[400];
+        assert!(raw_ratio > 10.0, "raw ratio was {}", raw_ratio);
+        whiten_spectrum(&mut magnitudes, 32);
+        let whitened_ratio = magnitudes[10] / magnitudes[400];
+        assert!(
+            whitened_ratio < 2.0,
+            "whitened ratio was {}",
+            whitened_ratio
+        );
+        // Degenerate inputs pass through untouched.
+        whiten_spectrum(&mut [], 32);
+        let mut silent = vec![0.0f32; 8];
+        whiten_spectrum(&mut silent, 4);
+        assert_eq!(silent, vec![0.0f32; 8]);
+    }
+
+    #[test]
+    fn adaptive_window_is_long_for_bass_and_short_for_treble() {
+        // Low E on a bass guitar needs the resolution; a flute note does not.
+        assert_eq!(adaptive_window_size(41.2, 4096), 8192);
+        assert_eq!(adaptive_window_size(880.0, 8192), 2048);
+        assert_eq!(adaptive_window_size(3000.0, 2048), 1024);
+        // Within a semitone of the 110 Hz boundary the current size sticks,
+        // whichever side it started on.
+        assert_eq!(adaptive_window_size(108.0, 4096), 4096);
+        assert_eq!(adaptive_window_size(108.0, 8192), 8192);
+        assert_eq!(adaptive_window_size(0.0, 4096), 4096);
+    }
+
+    #[test]
+    fn single_frame_detection_matches_the_averaged_result_on_a_clean_tone() {
+        let sample_rate = 44100;
+        let samples: Vec<f32> = (0..4096 * 3)
+            .map(|i| (PI * 2.0 * 440.0 * i as f32 / sample_rate as f32).sin() * 0.5)
+            .collect();
+        let single = single_frame_magnitudes(&samples, 4096);
+        assert_eq!(single.len(), 2048);
+        let mut processor = StftProcessor::new(4096, 2048);
+        let averaged = aggregate_magnitudes(&processor.magnitudes(&samples), FrameAggregation::Mean);
+        let peak_bin = |magnitudes: &[f32]| {
+            magnitudes
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(bin, _)| bin)
+                .unwrap()
+        };
+        assert_eq!(peak_bin(&single), peak_bin(&averaged));
+        assert!(single_frame_magnitudes(&samples[..1000], 4096).is_empty());
+    }
+
+    #[test]
+    fn magnitude_frames_equal_norms_of_complex_frames() {
+        let samples: Vec<f32> = (0..2048)
+            .map(|i| (PI * 2.0 * 440.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let mut processor = StftProcessor::new(512, 256);
+        let magnitudes = processor.magnitudes(&samples);
+        let frames = processor.process(&samples);
+        assert_eq!(magnitudes.len(), frames.len());
+        for (magnitude_frame, frame) in magnitudes.iter().zip(frames) {
+            // Only the non-mirrored half is kept.
+            assert_eq!(magnitude_frame.len(), 256);
+            for (magnitude, bin) in magnitude_frame.iter().zip(frame) {
+                assert_eq!(*magnitude, bin.norm());
+            }
+        }
+    }
+
+    #[test]
+    fn median_filter_ignores_single_outlier() {
+        let frequencies = [220.1, 219.9, 880.0, 220.0, 220.2];
+        assert!((median(&frequencies) - 220.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pitch_track_csv_has_matching_header_and_rows() {
+        let records = vec![PitchRecord {
+            timestamp_seconds: 0.0464,
+            frequency: 440.0,
+            note: "A4".to_string(),
+            cents_offset: 0.0,
+        }];
+        let path = std::env::temp_dir().join("rustique_pitch_track_test.csv");
+        let path = path.to_str().unwrap().to_string();
+        write_pitch_track_csv(&path, &records, None).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let mut lines = contents.lines();
+        assert_eq!(
+            lines.next(),
+            Some("timestamp_seconds,detected_freq,note_name,cents_offset")
+        );
+        assert_eq!(lines.next(), Some("0.0464,440.000,A4,0.00"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn clarity_separates_tone_from_noise() {
+        let mut tone = vec![0.01f32; 256];
+        tone[40] = 1.0;
+        let noise = vec![0.5f32; 256];
+        assert!(spectral_clarity(&tone) > 10.0);
+        assert!(spectral_clarity(&noise) < 1.5);
+    }
+
+    #[test]
+    fn guitar_preset_maps_110_hz_to_a_string() {
+        let (label, target) = nearest_preset_string(110.0, &INSTRUMENT_PRESETS[0]).unwrap();
+        assert_eq!(label, "A2");
+        assert!((target - 110.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn i16_conversion_covers_full_range() {
+        assert!((i16_sample_to_f32(i16::MAX) - 1.0).abs() < 1e-3);
+        assert!((i16_sample_to_f32(i16::MIN) + 1.0).abs() < 1e-6);
+        assert_eq!(i16_sample_to_f32(0), 0.0);
+    }
+
+    #[test]
+    fn u16_conversion_centers_on_zero() {
+        assert!((u16_sample_to_f32(u16::MAX) - 1.0).abs() < 1e-3);
+        assert!((u16_sample_to_f32(0) + 1.0).abs() < 1e-6);
+        assert_eq!(u16_sample_to_f32(32768), 0.0);
+    }
+
+    #[test]
+    fn downmix_averages_stereo_pairs() {
+        let interleaved = [0.2, 0.4, -1.0, 1.0, 0.5, 0.5];
+        assert_eq!(downmix_to_mono(&interleaved, 2), vec![0.3, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn downmix_passes_mono_through() {
+        let samples = [0.1, -0.2, 0.3];
+        assert_eq!(downmix_to_mono(&samples, 1), samples.to_vec());
+    }
+
+    #[test]
+    fn saturated_buffer_flags_clipping_and_clean_one_does_not() {
+        let sample_rate = 44100;
+        // A sine driven to 1.3x full scale and hard-limited: roughly 10%
+        // of the samples sit flattened at ±1.0.
+        let clipped: Vec<f32> = (0..4096)
+            .map(|i| {
+                (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin() * 1.3
+            })
+            .map(|s| s.clamp(-1.0, 1.0))
+            .collect();
+        assert!(is_clipping(&clipped));
+        let clean: Vec<f32> = (0..4096)
+            .map(|i| (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin() * 0.9)
+            .collect();
+        assert!(!is_clipping(&clean));
+        assert!(!is_clipping(&[]));
+    }
+
+    #[test]
+    fn left_selection_extracts_even_indexed_samples() {
+        let interleaved = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(
+            select_channel(&interleaved, 2, ChannelSelection::Left),
+            vec![0.0, 2.0, 4.0]
+        );
+        assert_eq!(
+            select_channel(&interleaved, 2, ChannelSelection::Right),
+            vec![1.0, 3.0, 5.0]
+        );
+        // A channel beyond the device's count falls back to the mix.
+        assert_eq!(
+            select_channel(&interleaved, 2, ChannelSelection::Index(5)),
+            downmix_to_mono(&interleaved, 2)
+        );
+    }
+
+    #[test]
+    fn harmonic_product_spectrum_recovers_weak_fundamental() {
+        let mut magnitudes = vec![0.1f32; 128];
+        magnitudes[10] = 0.5; // weak fundamental
+        magnitudes[20] = 3.0; // strong 2nd harmonic
+        magnitudes[30] = 2.0; // strong 3rd harmonic
+        let product = harmonic_product_spectrum(&magnitudes, 3);
+        let peak = product
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .map(|(i, _)| i);
+        assert_eq!(peak, Some(10));
+    }
+
+    #[test]
+    fn peak_refinement_recovers_a_fractional_bin() {
+        // Parabolic interpolation is exact when the samples really lie on
+        // a parabola, so build one with its vertex at bin 10.3.
+        let vertex = 10.3f32;
+        let magnitudes: Vec<f32> = (0..32)
+            .map(|bin| 1.0 - 0.01 * (bin as f32 - vertex).powi(2))
+            .collect();
+        let refined = refine_peak_bin(&magnitudes, 10);
+        assert!(
+            (refined - vertex).abs() < 1e-3,
+            "refined to bin {} instead of {}",
+            refined,
+            vertex
+        );
+        // Edge bins cannot be fitted and come back unshifted.
+        assert_eq!(refine_peak_bin(&magnitudes, 0), 0.0);
+        assert_eq!(refine_peak_bin(&magnitudes, 31), 31.0);
+
+        // End to end: 443 Hz sits between the ~10.77 Hz bins of a 4096
+        // window at 44.1 kHz, yet the refined estimate lands within 2 Hz.
+        let sample_rate = 44100;
+        let samples: Vec<f32> = (0..4096 * 3)
+            .map(|i| {
+                (2.0 * std::f32::consts::PI * 443.0 * i as f32 / sample_rate as f32).sin()
+            })
+            .collect();
+        let freq = detect_pitch(&samples, sample_rate, 4096, 2048).unwrap();
+        assert!((freq - 443.0).abs() < 2.0, "detected {} Hz", freq);
+    }
+
+    #[test]
+    fn rms_of_constant_signal_is_its_amplitude() {
+        let buffer = vec![0.5f32; 1024];
+        assert!((rms(&buffer) - 0.5).abs() < 1e-6);
+        assert_eq!(rms(&[]), 0.0);
+    }
+
+    #[test]
+    fn b_flat_transposition_displays_concert_b_flat_as_c() {
+        // A#3 is concert B♭3; a B♭ instrument reads it as written C4.
+        assert_eq!(transpose_note_label("A#3", 2), "C4");
+    }
+
+    #[test]
+    fn transposition_wraps_octaves_downward() {
+        assert_eq!(transpose_note_label("C4", -1), "B3");
+    }
+
+    #[test]
+    fn equal_temperament_matches_note_table() {
+        let frequencies = note_frequencies(Temperament::Equal, 0);
+        for (i, (_, expected)) in NOTES.iter().enumerate() {
+            assert!((frequencies[i] - expected).abs() / expected < 1e-3);
+        }
+    }
+}
@@ -1,142 +1,3276 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use eframe::egui;
-use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
-use plotters::prelude::*;
-use rustfft::{FftPlanner, num_complex::Complex32};
+use log::{debug, error, info, warn};
+use midir::{MidiOutput, MidiOutputConnection};
+#[cfg(unix)]
+use midir::os::unix::VirtualOutput;
+use rustique::pitch::{MpmDetector, PitchDetector, YinDetector};
+use rustique::scala::{ScalaScale, parse_kbm, parse_scl};
+use rustique::{
+    ChannelSelection, DetectionMethod, DriftStats, FrameAggregation, INSTRUMENT_PRESETS,
+    InstrumentPreset, NOTES, NoteSpelling,
+    NoteTable, PitchFrame, PitchRecord,
+    PitchSmoother,
+    StftProcessor, StreamResampler, Temperament, a_weight, adaptive_window_size,
+    aggregate_magnitudes, analysis_latency_ms, analyze_pitch_track, apply_a4_reference, band_limit, bin_frequencies, cents_offset, cepstrum_pitch, calibration_offset_cents,
+    check_buffer_length,
+    compute_short_time_fourier_transform, detect_onsets, detect_pitch, detect_polyphonic_pitches,
+    estimate_key,
+    frequency_to_edo_note, frequency_to_midi, frequency_to_note, frequency_to_note_at,
+    harmonic_product_spectrum, refine_peak_bin,
+    i16_sample_to_f32, interval_name, is_clipping, nearest_preset_string, note_frequencies, notch_out,
+    estimate_inharmonicity, find_peaks, pre_emphasis, sanitize_window,
+    plot_average_magnitudes_with_bins, plot_spectrogram, read_audio, rms, select_channel, single_frame_magnitudes, spectral_clarity,
+    self_test, smooth_spectrum, spell_note_label, to_db,
+    top_two_peaks, transpose_note_label, u16_sample_to_f32, whiten_spectrum, write_pitch_track_csv, write_wav,
+    zero_crossing_pitch,
+};
+use serde::{Deserialize, Serialize};
 use std::{
     error::Error,
     f32::consts::PI,
-    sync::{Arc, Mutex},
-    thread::sleep,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     time::Duration,
 };
 
+static TRANSPOSITIONS: [(&str, i32); 4] = [
+    ("Concert Pitch", 0),
+    ("B♭ Trumpet", 2),
+    ("E♭ Alto Sax", 9),
+    ("F Horn", 7),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TunerMode {
+    Chromatic,
+    Target,
+    /// Several targets at once, for a group tuning from a shared screen.
+    Ensemble,
+}
+
+/// Palette for the tuning indicator. The classic green/red pairing reads
+/// poorly for red-green colorblind users, so a deuteranopia-friendly
+/// blue/orange palette and a high-contrast scheme that leans on brightness
+/// (plus the existing arrow glyphs as shape cues) are offered as
+/// alternatives. Persisted with the rest of the settings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum ColorScheme {
+    Classic,
+    BlueOrange,
+    HighContrast,
+}
+
+impl ColorScheme {
+    const ALL: [ColorScheme; 3] = [
+        ColorScheme::Classic,
+        ColorScheme::BlueOrange,
+        ColorScheme::HighContrast,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ColorScheme::Classic => "Green/red (classic)",
+            ColorScheme::BlueOrange => "Blue/orange (deuteranopia-friendly)",
+            ColorScheme::HighContrast => "High contrast",
+        }
+    }
+
+    /// Color of an in-tune reading and the in-tune meter zone.
+    fn in_tune(self) -> egui::Color32 {
+        match self {
+            ColorScheme::Classic => egui::Color32::from_rgb(60, 180, 60),
+            ColorScheme::BlueOrange => egui::Color32::from_rgb(0, 114, 178),
+            ColorScheme::HighContrast => egui::Color32::WHITE,
+        }
+    }
+
+    /// Color of a reading that needs correcting, and of the needle.
+    fn out_of_tune(self) -> egui::Color32 {
+        match self {
+            ColorScheme::Classic => egui::Color32::from_rgb(220, 60, 60),
+            ColorScheme::BlueOrange => egui::Color32::from_rgb(230, 159, 0),
+            ColorScheme::HighContrast => egui::Color32::from_gray(140),
+        }
+    }
+
+    /// Accent for active-but-untuned elements, e.g. the live string in
+    /// the neck diagram and the history trace.
+    fn highlight(self) -> egui::Color32 {
+        match self {
+            ColorScheme::Classic => egui::Color32::LIGHT_YELLOW,
+            ColorScheme::BlueOrange => egui::Color32::from_rgb(86, 180, 233),
+            ColorScheme::HighContrast => egui::Color32::from_gray(220),
+        }
+    }
+}
+
+/// Direction indicator with hysteresis: flat and sharp are entered past
+/// +-8 cents but only released inside +-3 cents, so the arrow doesn't
+/// flicker when the reading hovers around a threshold.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PitchIndicator {
+    Flat,
+    InTune,
+    Sharp,
+}
+
+impl PitchIndicator {
+    /// Advance the indicator state for a new smoothed cents reading.
+    fn advance(self, cents: f32) -> Self {
+        match self {
+            PitchIndicator::Flat if cents > -3.0 => {
+                if cents > 8.0 {
+                    PitchIndicator::Sharp
+                } else {
+                    PitchIndicator::InTune
+                }
+            }
+            PitchIndicator::Sharp if cents < 3.0 => {
+                if cents < -8.0 {
+                    PitchIndicator::Flat
+                } else {
+                    PitchIndicator::InTune
+                }
+            }
+            PitchIndicator::InTune if cents < -8.0 => PitchIndicator::Flat,
+            PitchIndicator::InTune if cents > 8.0 => PitchIndicator::Sharp,
+            current => current,
+        }
+    }
+}
+
+/// Longest stretch of audio kept queued for analysis. If the analysis
+/// thread stalls, anything older is dropped so memory stays bounded.
+const MAX_BUFFER_SECONDS: usize = 5;
+
+/// Span of the pitch-drift timeline in the GUI.
+const HISTORY_SECONDS: f64 = 10.0;
+
+/// Peak and RMS level of the most recent audio callback, shared from the
+/// audio thread so the GUI can show an input gain meter. `clipped` latches
+/// once any sample hits full scale and stays set until the user resets it.
+#[derive(Clone, Copy)]
+struct InputLevel {
+    peak: f32,
+    rms: f32,
+    clipped: bool,
+}
+
+/// A note reading captured while the Hold toggle is active, kept until a
+/// more confident reading (or a manual clear) replaces it.
+#[derive(Clone)]
+struct HeldReading {
+    note: String,
+    frequency: f32,
+    cents: f32,
+    confidence: f32,
+}
+
+/// User-adjustable settings persisted between launches. Unknown or missing
+/// fields in the stored file fall back to these defaults, so older files
+/// keep loading as settings are added.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    window_size: usize,
+    temperament: Temperament,
+    note_spelling: NoteSpelling,
+    tonic: usize,
+    detection_method: DetectionMethod,
+    frame_aggregation: FrameAggregation,
+    gate_threshold_dbfs: f32,
+    confidence_threshold: f32,
+    smoothing_frames: usize,
+    edo_divisions: usize,
+    band_min_hz: f32,
+    band_max_hz: f32,
+    mains_notch_hz: f32,
+    internal_sample_rate: usize,
+    channel_selection: ChannelSelection,
+    calibration: f32,
+    a4_reference: f32,
+    dark_theme: bool,
+    font_scale: f32,
+    low_latency: bool,
+    adaptive_window: bool,
+    whitening: bool,
+    pre_emphasis: bool,
+    pre_emphasis_coefficient: f32,
+    mpm_clarity_threshold: f32,
+    hps_harmonics: usize,
+    color_scheme: ColorScheme,
+    spectrum_smoothing: f32,
+    strobe_mode: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            window_size: 4096,
+            temperament: Temperament::Equal,
+            note_spelling: NoteSpelling::Sharps,
+            tonic: 0,
+            // HPS by default: plucked strings routinely put more energy
+            // into the second harmonic than the fundamental, and the
+            // plain spectral peak then reads an octave (or two) high.
+            detection_method: DetectionMethod::HarmonicProduct,
+            frame_aggregation: FrameAggregation::Mean,
+            gate_threshold_dbfs: -50.0,
+            confidence_threshold: 5.0,
+            smoothing_frames: 5,
+            edo_divisions: 12,
+            band_min_hz: 40.0,
+            band_max_hz: 2000.0,
+            // Mains hum notch is off until the user picks 50 or 60 Hz.
+            mains_notch_hz: 0.0,
+            // Canonical analysis rate; 0 keeps the device's native rate.
+            internal_sample_rate: 44100,
+            channel_selection: ChannelSelection::Mix,
+            // Multiplicative pitch correction, unity when uncalibrated.
+            calibration: 1.0,
+            // Concert pitch in Hz; baroque groups use 415, orchestras up
+            // to 443.
+            a4_reference: 440.0,
+            dark_theme: true,
+            font_scale: 1.0,
+            // Quarter-length detection frames; see `effective_frame`.
+            low_latency: false,
+            // Pick the window length from the detected pitch instead.
+            adaptive_window: false,
+            // Flatten the spectral envelope before peak picking.
+            whitening: false,
+            // Tilt the spectrum toward the treble before the STFT.
+            pre_emphasis: false,
+            pre_emphasis_coefficient: 0.95,
+            // Below this NSDF peak height MPM reports no pitch at all.
+            mpm_clarity_threshold: 0.8,
+            // Downsample-and-multiply stages in the harmonic product.
+            hps_harmonics: 3,
+            color_scheme: ColorScheme::Classic,
+            // Display-only blend toward each new frame; 1 shows raw frames.
+            spectrum_smoothing: 0.4,
+            // Strobe band instead of the needle meter.
+            strobe_mode: false,
+        }
+    }
+}
+
+/// Detection frame geometry with the low-latency toggle applied: a quarter
+/// of the configured window and hop, floored so very small configured
+/// windows don't collapse to nothing.
+fn effective_frame(window_size: usize, hop_size: usize, low_latency: bool) -> (usize, usize) {
+    if low_latency {
+        ((window_size / 4).max(512), (hop_size / 4).max(256))
+    } else {
+        (window_size, hop_size)
+    }
+}
+
+/// How much of an accumulated buffer one analysis pass consumes: the
+/// number of STFT frames it yields and the matching drain length in
+/// samples. The transform advances through every full window in the
+/// buffer, so draining a single hop per iteration would re-analyze the
+/// same samples whenever more than one hop had accumulated (and the
+/// timestamp clock would fall behind real time). Draining
+/// `num_frames * hop_size` instead leaves exactly the `window - hop`
+/// overlap behind, so every sample is analyzed once.
+fn consumed_by_analysis(
+    buffer_len: usize,
+    window_size: usize,
+    hop_size: usize,
+) -> (usize, usize) {
+    if buffer_len < window_size || hop_size == 0 {
+        return (0, 0);
+    }
+    let num_frames = (buffer_len - window_size) / hop_size + 1;
+    (num_frames, (num_frames * hop_size).min(buffer_len))
+}
+
+/// Location of the settings file in the platform config directory.
+fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rustique").join("settings.toml"))
+}
+
+/// Load settings from the given file, falling back to defaults when the
+/// file is missing or malformed.
+fn load_settings(path: &Path) -> Settings {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Settings::default();
+    };
+    toml::from_str(&text).unwrap_or_else(|err| {
+        warn!("Ignoring malformed settings file {}: {}", path.display(), err);
+        Settings::default()
+    })
+}
+
+/// Write settings as TOML to the given file, creating parent directories.
+fn save_settings(path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, toml::to_string_pretty(settings)?)?;
+    Ok(())
+}
+
+/// Lock a mutex, recovering the inner value if another thread panicked
+/// while holding it, so the analysis loop survives lock poisoning.
+fn lock_or_recover<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Send a raw MIDI message over the active output connection, if any.
+fn send_midi_message(connection: &Arc<Mutex<Option<MidiOutputConnection>>>, message: &[u8]) {
+    if let Some(conn) = lock_or_recover(connection).as_mut()
+        && conn.send(message).is_err()
+    {
+        error!("Failed to send MIDI message");
+    }
+}
+
+/// Names of the currently available MIDI output ports.
+fn midi_port_names() -> Vec<String> {
+    match MidiOutput::new("Rustique") {
+        Ok(output) => output
+            .ports()
+            .iter()
+            .map(|port| {
+                output
+                    .port_name(port)
+                    .unwrap_or_else(|_| "Unknown port".to_string())
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Index of the loudest bin, if the spectrum is non-empty. Bin 0 is
+/// skipped: it holds whatever DC survives mean removal, never a pitch.
+fn strongest_bin(magnitudes: &[f32]) -> Option<usize> {
+    magnitudes
+        .iter()
+        .enumerate()
+        .skip(1)
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(bin, _)| bin)
+}
+
+/// Per-stream conversion state threaded through the input callbacks: the
+/// device's channel count, the resampler to the canonical rate, and the
+/// analysis buffer cap.
+struct InputConversion {
+    channels: usize,
+    resampler: StreamResampler,
+    max_buffer_samples: usize,
+}
+
+fn push_input_samples(
+    audio_data: &Arc<Mutex<Vec<f32>>>,
+    recording: &Arc<Mutex<Option<Vec<f32>>>>,
+    input_level: &Arc<Mutex<InputLevel>>,
+    channel_selection: &Arc<Mutex<ChannelSelection>>,
+    audio_ready: &Condvar,
+    conversion: &mut InputConversion,
+    data: &[f32],
+) {
+    // Meter the raw interleaved samples so a clip on any channel is seen
+    // even when downmixing would average it away.
+    let peak = data.iter().fold(0.0f32, |acc, s| acc.max(s.abs()));
+    {
+        let mut level = lock_or_recover(input_level);
+        level.peak = peak;
+        level.rms = rms(data);
+        if peak >= 1.0 {
+            level.clipped = true;
+        }
+    }
+    // Convert to the canonical analysis rate before anything downstream
+    // sees the samples, so recordings and analysis agree on the rate.
+    let mono = conversion.resampler.process(&select_channel(
+        data,
+        conversion.channels,
+        *lock_or_recover(channel_selection),
+    ));
+    // Capture into the recording take, if one is active. This buffer is
+    // separate from the analysis queue, which keeps draining as usual.
+    if let Some(take) = lock_or_recover(recording).as_mut() {
+        take.extend_from_slice(&mono);
+    }
+    let mut buffer = audio_data.lock().unwrap();
+    buffer.extend_from_slice(&mono);
+    if buffer.len() > conversion.max_buffer_samples {
+        let excess = buffer.len() - conversion.max_buffer_samples;
+        buffer.drain(..excess);
+        warn!("Audio buffer overflow: dropped {} oldest samples", excess);
+    }
+    drop(buffer);
+    // Wake the analysis thread, which sleeps on this condition variable
+    // until a full window has accumulated instead of polling on a timer.
+    audio_ready.notify_one();
+}
+
 struct Rustique {
     detected_note: Arc<Mutex<String>>,
     detected_freq: Arc<Mutex<f32>>,
+    temperament: Arc<Mutex<Temperament>>,
+    note_spelling: Arc<Mutex<NoteSpelling>>,
+    tonic: Arc<Mutex<usize>>,
+    transposition: usize,
+    gate_threshold_dbfs: Arc<Mutex<f32>>,
+    detection_method: Arc<Mutex<DetectionMethod>>,
+    frame_aggregation: Arc<Mutex<FrameAggregation>>,
+    band_min_hz: Arc<Mutex<f32>>,
+    band_max_hz: Arc<Mutex<f32>>,
+    mains_notch_hz: Arc<Mutex<f32>>,
+    tuner_mode: Arc<Mutex<TunerMode>>,
+    instrument_preset: Arc<Mutex<Option<usize>>>,
+    target_note_index: Arc<Mutex<usize>>,
+    // (note index, octave) pairs shown side by side in Ensemble mode.
+    ensemble_targets: Arc<Mutex<Vec<(usize, i32)>>>,
+    target_octave: Arc<Mutex<i32>>,
+    smoothing_frames: Arc<Mutex<usize>>,
+    // Trade frequency resolution for quicker readings; see `effective_frame`.
+    low_latency: Arc<Mutex<bool>>,
+    // Let the analysis thread resize its window to the detected pitch.
+    adaptive_window: Arc<Mutex<bool>>,
+    whitening: Arc<Mutex<bool>>,
+    pre_emphasis: Arc<Mutex<bool>>,
+    pre_emphasis_coefficient: Arc<Mutex<f32>>,
+    mpm_clarity_threshold: Arc<Mutex<f32>>,
+    hps_harmonics: Arc<Mutex<usize>>,
+    spectrum_smoothing: Arc<Mutex<f32>>,
+    edo_divisions: Arc<Mutex<usize>>,
+    // Loaded Scala scale and the frequency of its 1/1; overrides the
+    // chromatic/EDO lookup while present.
+    scala_scale: Arc<Mutex<Option<(ScalaScale, f32)>>>,
+    scala_path: String,
+    scala_status: Option<String>,
+    detected_cents: Arc<Mutex<f32>>,
+    // Chord-mode toggle and the notes it most recently detected.
+    polyphonic: Arc<Mutex<bool>>,
+    calibration: Arc<Mutex<f32>>,
+    a4_reference: Arc<Mutex<f32>>,
+    chord_notes: Arc<Mutex<Vec<String>>>,
+    // Two-peak interval readout, None when only one peak is present.
+    interval_display: Arc<Mutex<Option<String>>>,
+    confidence: Arc<Mutex<f32>>,
+    confidence_threshold: Arc<Mutex<f32>>,
+    // Fitted stretched-partial coefficient B, None when too few partials
+    // are measurable; shown in piano mode.
+    inharmonicity: Arc<Mutex<Option<f32>>>,
+    detected_midi: Option<i32>,
+    midi_connection: Arc<Mutex<Option<MidiOutputConnection>>>,
+    midi_min_hold_ms: Arc<Mutex<u64>>,
+    // 0 = off, 1 = virtual port, 2.. = index into the real port list + 2.
+    midi_port_selection: usize,
+    midi_status: Option<String>,
+    // Frequency of the reference tone while playing, None when silent.
+    reference_tone: Arc<Mutex<Option<f32>>>,
+    tone_stream: Option<cpal::Stream>,
+    tone_status: Option<String>,
+    // Whole-octave shift applied to the reference tone, so e.g. a bass
+    // player can hear E1 while the target note stays E4.
+    tone_octave_shift: i32,
+    // Capture stream, held so `on_exit` can pause it explicitly instead
+    // of relying on drop order, and flag telling the analysis thread to
+    // exit its loop when the window closes.
+    input_stream: Option<cpal::Stream>,
+    shutdown: Arc<AtomicBool>,
+    metronome: Arc<Mutex<MetronomeSettings>>,
+    metronome_stream: Option<cpal::Stream>,
+    metronome_status: Option<String>,
+    latest_spectrum: Arc<Mutex<Vec<f32>>>,
+    pitch_track: Arc<Mutex<Vec<PitchRecord>>>,
+    // Session-wide tuning statistics fed by the analysis thread.
+    drift_stats: Arc<Mutex<DriftStats>>,
+    audio_data: Arc<Mutex<Vec<f32>>>,
+    input_level: Arc<Mutex<InputLevel>>,
+    // Set by the analysis thread when the current window shows sustained
+    // clipping, which distorts the harmonics detection relies on.
+    clipping: Arc<Mutex<bool>>,
+    // Set when the current window is pure silence (or only NaN/Inf
+    // glitches, which sanitizing zeroes out): nothing to detect.
+    no_signal: Arc<Mutex<bool>>,
+    channel_selection: Arc<Mutex<ChannelSelection>>,
+    // Mono take being captured while the Record toggle is on.
+    recording: Arc<Mutex<Option<Vec<f32>>>>,
+    // Shared so the GUI and analysis thread both follow a stream rebuilt
+    // on a device with a different rate.
+    sample_rate: Arc<Mutex<usize>>,
+    window_size: usize,
+    hop_size: usize,
+    save_status: Option<String>,
+    internal_sample_rate: usize,
+    dark_theme: bool,
+    font_scale: f32,
+    color_scheme: ColorScheme,
+    strobe_mode: bool,
+    // Reading frozen at the moment of highest confidence while Hold is on.
+    hold_enabled: bool,
+    held_reading: Option<HeldReading>,
+    // Spectrum display options; detection always uses linear magnitudes.
+    // Piano tuning readout: show the inharmonicity fit for the current note.
+    piano_mode: bool,
+    // Outcome of the last pipeline self-test, None until one is run.
+    self_test_status: Option<String>,
+    spectrum_db: bool,
+    // Freeze keeps the last captured spectrum/waveform on screen while
+    // detection carries on underneath.
+    freeze_enabled: bool,
+    frozen_spectrum: Vec<f32>,
+    frozen_waveform: Vec<f32>,
+    spectrum_a_weight: bool,
+    // Set when audio setup failed; the GUI shows this instead of the tuner.
+    startup_error: Option<String>,
+    // Display position of the meter needle, eased toward the measured
+    // cents each frame so it sweeps instead of jumping.
+    needle_cents: f32,
+    pitch_indicator: PitchIndicator,
+    // Rolling (time, cents) points for the drift timeline, trimmed to the
+    // last HISTORY_SECONDS.
+    cents_history: Vec<(f64, f32)>,
+    // Accumulated strobe band position; drift speed tracks the offset.
+    strobe_phase: f32,
+    // Snapshot of the settings as last written to disk, so unchanged
+    // frames don't touch the filesystem.
+    last_saved_settings: Settings,
+}
+
+impl Rustique {
+    /// Current values of every persisted setting.
+    fn snapshot_settings(&self) -> Settings {
+        Settings {
+            window_size: self.window_size,
+            temperament: *self.temperament.lock().unwrap(),
+            note_spelling: *self.note_spelling.lock().unwrap(),
+            tonic: *self.tonic.lock().unwrap(),
+            detection_method: *self.detection_method.lock().unwrap(),
+            frame_aggregation: *self.frame_aggregation.lock().unwrap(),
+            gate_threshold_dbfs: *self.gate_threshold_dbfs.lock().unwrap(),
+            confidence_threshold: *self.confidence_threshold.lock().unwrap(),
+            smoothing_frames: *self.smoothing_frames.lock().unwrap(),
+            edo_divisions: *self.edo_divisions.lock().unwrap(),
+            band_min_hz: *self.band_min_hz.lock().unwrap(),
+            band_max_hz: *self.band_max_hz.lock().unwrap(),
+            mains_notch_hz: *self.mains_notch_hz.lock().unwrap(),
+            internal_sample_rate: self.internal_sample_rate,
+            channel_selection: *self.channel_selection.lock().unwrap(),
+            calibration: *self.calibration.lock().unwrap(),
+            a4_reference: *self.a4_reference.lock().unwrap(),
+            dark_theme: self.dark_theme,
+            font_scale: self.font_scale,
+            color_scheme: self.color_scheme,
+            strobe_mode: self.strobe_mode,
+            low_latency: *self.low_latency.lock().unwrap(),
+            adaptive_window: *self.adaptive_window.lock().unwrap(),
+            whitening: *self.whitening.lock().unwrap(),
+            pre_emphasis: *self.pre_emphasis.lock().unwrap(),
+            pre_emphasis_coefficient: *self.pre_emphasis_coefficient.lock().unwrap(),
+            mpm_clarity_threshold: *self.mpm_clarity_threshold.lock().unwrap(),
+            hps_harmonics: *self.hps_harmonics.lock().unwrap(),
+            spectrum_smoothing: *self.spectrum_smoothing.lock().unwrap(),
+        }
+    }
+
+    /// (Re)open the MIDI output chosen by `midi_port_selection`, recording
+    /// any failure for display instead of panicking.
+    fn connect_midi(&mut self) {
+        let mut connection = self.midi_connection.lock().unwrap();
+        *connection = None;
+        self.midi_status = None;
+        let result = match self.midi_port_selection {
+            0 => return,
+            // Selection 1 stays reserved on every platform so saved
+            // settings and the enumerated-port offsets line up, but only
+            // ALSA and CoreMIDI can actually create a virtual port.
+            #[cfg(unix)]
+            1 => MidiOutput::new("Rustique")
+                .map_err(|e| e.to_string())
+                .and_then(|output| {
+                    output
+                        .create_virtual("Rustique pitch")
+                        .map_err(|e| e.to_string())
+                }),
+            #[cfg(not(unix))]
+            1 => Err("Virtual MIDI ports are not supported on this platform".to_string()),
+            selection => MidiOutput::new("Rustique")
+                .map_err(|e| e.to_string())
+                .and_then(|output| {
+                    let ports = output.ports();
+                    let port = ports
+                        .get(selection - 2)
+                        .ok_or_else(|| "MIDI port no longer available".to_string())?;
+                    output
+                        .connect(port, "rustique-pitch")
+                        .map_err(|e| e.to_string())
+                }),
+        };
+        match result {
+            Ok(opened) => *connection = Some(opened),
+            Err(message) => {
+                self.midi_port_selection = 0;
+                self.midi_status = Some(format!("MIDI output failed: {}", message));
+            }
+        }
+    }
+
+    /// Start (or retune) the reference tone at the current target note,
+    /// opening the output stream on first use.
+    fn play_reference_tone(&mut self) {
+        self.tone_status = None;
+        if self.tone_stream.is_none() {
+            match start_reference_tone_stream(&self.reference_tone) {
+                Ok(stream) => self.tone_stream = Some(stream),
+                Err(message) => {
+                    self.tone_status = Some(message);
+                    return;
+                }
+            }
+        }
+        let temperament = *self.temperament.lock().unwrap();
+        let tonic = *self.tonic.lock().unwrap();
+        let index = *self.target_note_index.lock().unwrap();
+        let octave = *self.target_octave.lock().unwrap();
+        let a4 = *self.a4_reference.lock().unwrap();
+        let base = apply_a4_reference(note_frequencies(temperament, tonic), a4)[index];
+        let sample_rate = *self.sample_rate.lock().unwrap();
+        match reference_tone_frequency(base, octave, self.tone_octave_shift, sample_rate) {
+            Ok(freq) => *self.reference_tone.lock().unwrap() = Some(freq),
+            Err(message) => self.tone_status = Some(message),
+        }
+    }
+
+    /// Load the `.scl` file named in `scala_path`, plus a sibling `.kbm`
+    /// with the same stem when one exists. Without a mapping the scale's
+    /// 1/1 sits on middle C, which is Scala's own convention.
+    fn load_scala_scale(&mut self) {
+        let path = std::path::PathBuf::from(self.scala_path.trim());
+        let scale = match std::fs::read_to_string(&path)
+            .map_err(|err| format!("Could not read {}: {}", path.display(), err))
+            .and_then(|text| parse_scl(&text))
+        {
+            Ok(scale) => scale,
+            Err(message) => {
+                self.scala_status = Some(message);
+                return;
+            }
+        };
+        let kbm_path = path.with_extension("kbm");
+        let base = if kbm_path.exists() {
+            let anchored = std::fs::read_to_string(&kbm_path)
+                .map_err(|err| format!("Could not read {}: {}", kbm_path.display(), err))
+                .and_then(|text| parse_kbm(&text))
+                .and_then(|mapping| {
+                    mapping
+                        .base_frequency(&scale)
+                        .ok_or("Keyboard mapping leaves the reference key unmapped".to_string())
+                });
+            match anchored {
+                Ok(base) => base,
+                Err(message) => {
+                    self.scala_status = Some(message);
+                    return;
+                }
+            }
+        } else {
+            261.625_55
+        };
+        self.scala_status = Some(format!(
+            "Loaded {} ({} degrees, 1/1 at {:.2} Hz)",
+            scale.description,
+            scale.steps_cents.len(),
+            base
+        ));
+        *self.scala_scale.lock().unwrap() = Some((scale, base));
+    }
+
+    fn draw_waveform(&self, ui: &mut egui::Ui) {
+        // Snapshot at most the latest analysis window, downsampled so the
+        // polyline stays a few hundred points regardless of window size.
+        const MAX_POINTS: usize = 300;
+        let samples: Vec<f32> = if self.freeze_enabled {
+            let stride = (self.frozen_waveform.len() / MAX_POINTS).max(1);
+            self.frozen_waveform.iter().step_by(stride).copied().collect()
+        } else {
+            let buffer = self.audio_data.lock().unwrap();
+            let start = buffer.len().saturating_sub(self.window_size);
+            let window = &buffer[start..];
+            let stride = (window.len() / MAX_POINTS).max(1);
+            window.iter().step_by(stride).copied().collect()
+        };
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), 80.0),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+        if samples.len() < 2 {
+            return;
+        }
+        let peak = samples
+            .iter()
+            .fold(0.0f32, |acc, s| acc.max(s.abs()))
+            .max(1e-4);
+        let points: Vec<egui::Pos2> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, &sample)| {
+                let x = rect.left() + rect.width() * i as f32 / (samples.len() - 1) as f32;
+                let y = rect.center().y - (sample / peak) * (rect.height() / 2.0 - 2.0);
+                egui::pos2(x, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, egui::Color32::LIGHT_GREEN),
+        ));
+    }
+
+    fn draw_spectrum(&self, ui: &mut egui::Ui, detected_freq: f32) {
+        // The analysis thread replaces the whole Vec under the lock, so a
+        // clone here can never observe a half-written frame.
+        let magnitudes = if self.freeze_enabled {
+            self.frozen_spectrum.clone()
+        } else {
+            self.latest_spectrum.lock().unwrap().clone()
+        };
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), 100.0),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+        if magnitudes.len() < 2 {
+            return;
+        }
+        let sample_rate = *self.sample_rate.lock().unwrap();
+        let nyquist = sample_rate as f32 / 2.0;
+        let min_freq = 20.0f32;
+        // Low-latency mode analyzes a shorter window, so derive the bin
+        // width from the frame the thread actually produced.
+        let freq_resolution = nyquist / magnitudes.len() as f32;
+        let log_span = (nyquist / min_freq).ln();
+        let x_for_freq = |freq: f32| -> Option<f32> {
+            if freq < min_freq || freq > nyquist {
+                return None;
+            }
+            Some(rect.left() + rect.width() * (freq / min_freq).ln() / log_span)
+        };
+        let peak = magnitudes.iter().fold(0.0f32, |acc, m| acc.max(*m)).max(1e-6);
+        // Normalized 0..1 display height for a bin, honoring the dB and
+        // A-weighting display options.
+        const DB_FLOOR: f32 = -80.0;
+        let height_for = |bin: usize, magnitude: f32| -> f32 {
+            if self.spectrum_db {
+                let mut db = to_db(magnitude, peak);
+                if self.spectrum_a_weight {
+                    db += a_weight(bin as f32 * freq_resolution);
+                }
+                ((db - DB_FLOOR) / -DB_FLOOR).clamp(0.0, 1.0)
+            } else {
+                magnitude / peak
+            }
+        };
+        let mut points = Vec::new();
+        for (bin, &magnitude) in magnitudes.iter().enumerate() {
+            let freq = bin as f32 * freq_resolution;
+            if let Some(x) = x_for_freq(freq) {
+                let y =
+                    rect.bottom() - height_for(bin, magnitude) * (rect.height() - 4.0) - 2.0;
+                points.push(egui::pos2(x, y));
+            }
+        }
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, egui::Color32::LIGHT_BLUE),
+        ));
+        if let Some(x) = x_for_freq(detected_freq) {
+            painter.line_segment(
+                [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(220, 60, 60)),
+            );
+        }
+        // While frozen the plot is stable, so a cursor readout is useful.
+        if self.freeze_enabled
+            && let Some(pos) = response.hover_pos()
+        {
+            let freq = min_freq * ((pos.x - rect.left()) / rect.width() * log_span).exp();
+            let bin = (freq / freq_resolution).round() as usize;
+            if let Some(&magnitude) = magnitudes.get(bin) {
+                response.on_hover_text(format!("{:.0} Hz: {:.4}", freq, magnitude));
+            }
+        }
+    }
+
+    /// Scrolling plot of the cents offsets gathered over the last
+    /// [`HISTORY_SECONDS`], with the in-tune band shaded, so slow drift is
+    /// visible at a glance.
+    fn draw_history(&self, ui: &mut egui::Ui, now: f64) {
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), 70.0),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+        // Shade the +-5 cent in-tune zone across the full width.
+        let y_for = |cents: f32| -> f32 {
+            rect.center().y - (cents.clamp(-50.0, 50.0) / 50.0) * (rect.height() / 2.0 - 2.0)
+        };
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(rect.left(), y_for(5.0)),
+                egui::pos2(rect.right(), y_for(-5.0)),
+            ),
+            0.0,
+            {
+                let zone = self.color_scheme.in_tune();
+                egui::Color32::from_rgba_unmultiplied(zone.r(), zone.g(), zone.b(), 40)
+            },
+        );
+        if self.cents_history.len() < 2 {
+            return;
+        }
+        let points: Vec<egui::Pos2> = self
+            .cents_history
+            .iter()
+            .map(|&(time, cents)| {
+                let age = (now - time) / HISTORY_SECONDS;
+                let x = rect.right() - rect.width() * age as f32;
+                egui::pos2(x, y_for(cents))
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, self.color_scheme.highlight()),
+        ));
+    }
+
+    /// Strobe-style fine tuning band: the pattern scrolls right when
+    /// sharp and left when flat, at a speed proportional to the offset, so
+    /// it stands still only when the pitch is dead on. Sub-cent errors
+    /// that barely move a needle still show up as a slow drift.
+    fn draw_strobe(&mut self, ui: &mut egui::Ui, cents: f32) {
+        let dt = ui.input(|i| i.stable_dt).min(0.1);
+        // 8 px/s per cent keeps one-cent errors clearly moving without
+        // turning large offsets into a blur.
+        self.strobe_phase += cents.clamp(-50.0, 50.0) * 8.0 * dt;
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), 22.0),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+        let band_width = 18.0;
+        let period = band_width * 2.0;
+        let mut x = rect.left() - period + self.strobe_phase.rem_euclid(period);
+        while x < rect.right() {
+            let band = egui::Rect::from_min_max(
+                egui::pos2(x.max(rect.left()), rect.top() + 2.0),
+                egui::pos2((x + band_width).min(rect.right()), rect.bottom() - 2.0),
+            );
+            if band.width() > 0.0 {
+                painter.rect_filled(band, 0.0, egui::Color32::from_gray(140));
+            }
+            x += period;
+        }
+    }
+
+    fn draw_tuning_meter(&mut self, ui: &mut egui::Ui, cents: f32) {
+        self.needle_cents += (cents.clamp(-50.0, 50.0) - self.needle_cents) * 0.2;
+        let (response, painter) =
+            ui.allocate_painter(egui::vec2(260.0, 130.0), egui::Sense::hover());
+        let rect = response.rect;
+        let center = egui::pos2(rect.center().x, rect.bottom() - 10.0);
+        let radius = rect.height() - 25.0;
+
+        // Map -50..+50 cents onto a 90 degree sweep, flat on the left.
+        let angle_for = |cents: f32| -> f32 { PI / 2.0 + (cents / 50.0) * (PI / 4.0) };
+        let point_at = |angle: f32, r: f32| -> egui::Pos2 {
+            egui::pos2(center.x + angle.sin() * r, center.y - angle.cos() * r)
+        };
+
+        // In-tune zone around the center, gray elsewhere. The high
+        // contrast scheme thickens the zone so it reads by shape as well
+        // as by color.
+        let zone_thickness = if self.color_scheme == ColorScheme::HighContrast {
+            7.0
+        } else {
+            4.0
+        };
+        let mut cents_mark = -50.0_f32;
+        while cents_mark < 50.0 {
+            let in_zone = cents_mark.abs() <= 5.0;
+            let color = if in_zone {
+                self.color_scheme.in_tune()
+            } else {
+                egui::Color32::GRAY
+            };
+            let a0 = angle_for(cents_mark);
+            let a1 = angle_for(cents_mark + 2.0);
+            painter.line_segment(
+                [point_at(a0, radius), point_at(a1, radius)],
+                egui::Stroke::new(if in_zone { zone_thickness } else { 4.0 }, color),
+            );
+            cents_mark += 2.0;
+        }
+
+        // The needle itself goes green inside the ±5 cent zone, so a
+        // glance at either the arc or the needle answers "in tune?".
+        let needle_angle = angle_for(self.needle_cents);
+        let needle_color = if self.needle_cents.abs() <= 5.0 {
+            self.color_scheme.in_tune()
+        } else {
+            self.color_scheme.out_of_tune()
+        };
+        painter.line_segment(
+            [center, point_at(needle_angle, radius - 6.0)],
+            egui::Stroke::new(2.0, needle_color),
+        );
+        painter.circle_filled(center, 4.0, egui::Color32::DARK_GRAY);
+    }
+
+    /// Neck diagram for the active instrument preset: one line per open
+    /// string, low string at the bottom as when looking down at a neck.
+    /// The string nearest the detected pitch lights green once in tune
+    /// and otherwise shows which way to correct. Only drawn while a
+    /// preset is active, so chromatic tuning keeps the plain display.
+    fn draw_string_diagram(
+        &self,
+        ui: &mut egui::Ui,
+        preset: &InstrumentPreset,
+        freq: f32,
+        cents: f32,
+    ) {
+        let height = 16.0 * preset.strings.len() as f32 + 10.0;
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), height),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+        let active = nearest_preset_string(freq, preset).map(|(label, _)| label);
+        let count = preset.strings.len() as f32;
+        for (i, (label, _)) in preset.strings.iter().enumerate() {
+            let y = rect.bottom() - (i as f32 + 1.0) / (count + 1.0) * rect.height();
+            let is_active = active == Some(label);
+            let in_tune = is_active && cents.abs() <= 5.0;
+            let color = if in_tune {
+                self.color_scheme.in_tune()
+            } else if is_active {
+                self.color_scheme.highlight()
+            } else {
+                egui::Color32::from_gray(90)
+            };
+            // Lower strings are physically thicker; the active one is
+            // emphasized further.
+            let thickness =
+                1.0 + (count - 1.0 - i as f32) * 0.4 + if is_active { 1.0 } else { 0.0 };
+            painter.line_segment(
+                [
+                    egui::pos2(rect.left() + 44.0, y),
+                    egui::pos2(rect.right() - 64.0, y),
+                ],
+                egui::Stroke::new(thickness, color),
+            );
+            painter.text(
+                egui::pos2(rect.left() + 8.0, y),
+                egui::Align2::LEFT_CENTER,
+                *label,
+                egui::FontId::proportional(12.0),
+                color,
+            );
+            if is_active && !in_tune {
+                let hint = if cents > 0.0 { "↓ sharp" } else { "↑ flat" };
+                painter.text(
+                    egui::pos2(rect.right() - 8.0, y),
+                    egui::Align2::RIGHT_CENTER,
+                    hint,
+                    egui::FontId::proportional(12.0),
+                    self.color_scheme.out_of_tune(),
+                );
+            }
+        }
+    }
+
+    /// Music-stand layout for the main reading: the pitch class drawn
+    /// large and centered with the octave as a smaller subscript, the
+    /// cents offset directly beneath, and the frequency demoted to a
+    /// small secondary line. The type size follows the panel width so
+    /// resizing the window keeps the note dominant and centered.
+    fn draw_note_display(&self, ui: &mut egui::Ui, note: &str, freq: f32, cents: f32) {
+        // Split "A♯4" into "A♯" and "4" (octaves can be negative, so a
+        // leading minus also starts the subscript); the gate's em dash
+        // has no octave part.
+        let subscript_start = note
+            .find(|c: char| c.is_ascii_digit() || c == '-')
+            .unwrap_or(note.len());
+        let (pitch_class, octave) = note.split_at(subscript_start);
+        let note_size = (ui.available_width() * 0.16).clamp(48.0, 140.0);
+        let silent = note == "—";
+        let color = if silent {
+            egui::Color32::from_gray(140)
+        } else if cents.abs() <= 5.0 {
+            self.color_scheme.in_tune()
+        } else {
+            ui.visuals().strong_text_color()
+        };
+        let mut job = egui::text::LayoutJob::default();
+        job.append(
+            pitch_class,
+            0.0,
+            egui::TextFormat {
+                font_id: egui::FontId::proportional(note_size),
+                color,
+                ..Default::default()
+            },
+        );
+        job.append(
+            octave,
+            2.0,
+            egui::TextFormat {
+                font_id: egui::FontId::proportional(note_size * 0.45),
+                color,
+                ..Default::default()
+            },
+        );
+        ui.vertical_centered(|ui| {
+            ui.label(job);
+            if !silent {
+                ui.label(
+                    egui::RichText::new(format!("{:+.1} cents", cents))
+                        .size(note_size * 0.22)
+                        .color(if cents.abs() <= 5.0 {
+                            self.color_scheme.in_tune()
+                        } else {
+                            self.color_scheme.out_of_tune()
+                        }),
+                );
+            }
+            ui.label(egui::RichText::new(format!("{:.2} Hz", freq)).weak());
+        });
+    }
+
+    /// Editable list of ensemble targets with a live per-target readout,
+    /// so several players tuning together can each watch their own row.
+    fn draw_ensemble_panel(&self, ui: &mut egui::Ui, freq: f32) {
+        let temperament = *self.temperament.lock().unwrap();
+        let tonic = *self.tonic.lock().unwrap();
+        let a4 = *self.a4_reference.lock().unwrap();
+        let frequencies = apply_a4_reference(note_frequencies(temperament, tonic), a4);
+        let mut targets = self.ensemble_targets.lock().unwrap();
+        let mut remove = None;
+        for (row, (index, octave)) in targets.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt(("ensemble_target", row))
+                    .selected_text(NOTES[*index].0)
+                    .show_ui(ui, |ui| {
+                        for (i, (name, _)) in NOTES.iter().enumerate() {
+                            ui.selectable_value(index, i, *name);
+                        }
+                    });
+                ui.add(egui::Slider::new(octave, 0..=7).text("Octave"));
+                let target_freq = frequencies[*index] * 2f32.powi(*octave - 4);
+                let cents = cents_offset(freq, target_freq);
+                let color = if cents.abs() <= 5.0 {
+                    self.color_scheme.in_tune()
+                } else {
+                    egui::Color32::from_gray(140)
+                };
+                ui.colored_label(color, format!("{:+.1} cents", cents));
+                if ui.button("Remove").clicked() {
+                    remove = Some(row);
+                }
+            });
+        }
+        if let Some(row) = remove {
+            targets.remove(row);
+        }
+        if ui.button("Add target").clicked() {
+            targets.push((9, 4));
+        }
+    }
 }
 
 impl eframe::App for Rustique {
+    /// Stop the background work when the window closes: the analysis
+    /// thread polls the shutdown flag and exits its loop, and the capture
+    /// stream is paused and dropped instead of lingering until process
+    /// teardown.
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(stream) = self.input_stream.take() {
+            let _ = stream.pause();
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.request_repaint();
+        // ~30 fps is plenty for the meters and keeps the idle CPU cost low;
+        // the analysis thread still runs at its own 10 ms cadence.
+        ctx.request_repaint_after(Duration::from_millis(33));
+        // Theme and scale are applied every frame so toggling them takes
+        // effect immediately; both are persisted with the other settings.
+        ctx.set_visuals(if self.dark_theme {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
+        ctx.set_zoom_factor(self.font_scale);
+        if let Some(error) = &self.startup_error {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.heading("Rustique Tuner");
+                ui.colored_label(egui::Color32::from_rgb(220, 60, 60), error);
+                ui.label("Fix the audio input and restart the application.");
+            });
+            return;
+        }
         let note = self.detected_note.lock().unwrap().clone();
         let freq = *self.detected_freq.lock().unwrap();
+        let cents = *self.detected_cents.lock().unwrap();
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Rustique Tuner");
-            ui.label(format!("Detected note: {}", note));
-            ui.label(format!("Frequency: {:.2} Hz", freq));
+            let was_frozen = self.freeze_enabled;
+            ui.checkbox(&mut self.freeze_enabled, "Freeze display");
+            if self.freeze_enabled && !was_frozen {
+                // Capture the moment of the toggle; detection keeps running
+                // on live audio underneath.
+                self.frozen_spectrum = self.latest_spectrum.lock().unwrap().clone();
+                let buffer = self.audio_data.lock().unwrap();
+                let start = buffer.len().saturating_sub(self.window_size);
+                self.frozen_waveform = buffer[start..].to_vec();
+            } else if !self.freeze_enabled && was_frozen {
+                self.frozen_spectrum.clear();
+                self.frozen_waveform.clear();
+            }
+            self.draw_waveform(ui);
+            let level = *self.input_level.lock().unwrap();
+            let peak_dbfs = 20.0 * level.peak.max(f32::EPSILON).log10();
+            let rms_dbfs = 20.0 * level.rms.max(f32::EPSILON).log10();
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Input level: {:.1} dBFS peak / {:.1} dBFS RMS",
+                    peak_dbfs, rms_dbfs
+                ));
+                if level.clipped {
+                    ui.colored_label(egui::Color32::from_rgb(220, 60, 60), "CLIP");
+                    if ui.button("Reset clip").clicked() {
+                        self.input_level.lock().unwrap().clipped = false;
+                    }
+                }
+                if *self.clipping.lock().unwrap() {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(220, 60, 60),
+                        "Clipping — reduce gain",
+                    );
+                }
+                if *self.no_signal.lock().unwrap() {
+                    ui.colored_label(egui::Color32::from_gray(140), "No signal");
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut selection = self.channel_selection.lock().unwrap();
+                egui::ComboBox::from_label("Input channel")
+                    .selected_text(channel_label(*selection))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut *selection, ChannelSelection::Mix, "Mix");
+                        ui.selectable_value(&mut *selection, ChannelSelection::Left, "Left");
+                        ui.selectable_value(&mut *selection, ChannelSelection::Right, "Right");
+                        ui.selectable_value(
+                            &mut *selection,
+                            ChannelSelection::Index(2),
+                            "Channel…",
+                        );
+                    });
+                if let ChannelSelection::Index(ref mut index) = *selection {
+                    // Shown one-based; a channel the device doesn't have
+                    // falls back to the mix in `select_channel`.
+                    let mut shown = *index + 1;
+                    ui.add(egui::Slider::new(&mut shown, 1..=16).text("channel"));
+                    *index = shown - 1;
+                }
+            });
+            self.draw_spectrum(ui, freq);
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.spectrum_db, "Spectrum in dB");
+                let mut smoothing = self.spectrum_smoothing.lock().unwrap();
+                ui.add(
+                    egui::Slider::new(&mut *smoothing, 0.05..=1.0).text("Spectrum smoothing"),
+                );
+                drop(smoothing);
+                if self.spectrum_db {
+                    ui.checkbox(&mut self.spectrum_a_weight, "A-weighting");
+                }
+            });
+            if self.strobe_mode {
+                self.draw_strobe(ui, cents);
+            } else {
+                self.draw_tuning_meter(ui, cents);
+            }
+            if let Some(preset_idx) = *self.instrument_preset.lock().unwrap() {
+                self.draw_string_diagram(ui, &INSTRUMENT_PRESETS[preset_idx], freq, cents);
+            }
+            let now = ui.input(|i| i.time);
+            if note != "—" {
+                self.cents_history.push((now, cents));
+            }
+            self.cents_history
+                .retain(|(time, _)| now - time <= HISTORY_SECONDS);
+            self.draw_history(ui, now);
+            let stats = *self.drift_stats.lock().unwrap();
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Session: {:+.1} cents mean, {:.1} cents spread, {:.0}% in tune",
+                    stats.mean(),
+                    stats.std_dev(),
+                    stats.in_tune_percent()
+                ));
+                if ui.button("Reset stats").clicked() {
+                    self.drift_stats.lock().unwrap().clear();
+                }
+            });
+            let mut tuner_mode = self.tuner_mode.lock().unwrap();
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut *tuner_mode, TunerMode::Chromatic, "Chromatic");
+                ui.selectable_value(&mut *tuner_mode, TunerMode::Target, "Target");
+                ui.selectable_value(&mut *tuner_mode, TunerMode::Ensemble, "Ensemble");
+            });
+            let target_mode = *tuner_mode == TunerMode::Target;
+            let ensemble_mode = *tuner_mode == TunerMode::Ensemble;
+            drop(tuner_mode);
+            if ensemble_mode {
+                self.draw_ensemble_panel(ui, freq);
+            }
+            if target_mode {
+                let mut target_note_index = self.target_note_index.lock().unwrap();
+                egui::ComboBox::from_label("Target note")
+                    .selected_text(NOTES[*target_note_index].0)
+                    .show_ui(ui, |ui| {
+                        for (i, (name, _)) in NOTES.iter().enumerate() {
+                            ui.selectable_value(&mut *target_note_index, i, *name);
+                        }
+                    });
+                drop(target_note_index);
+                let mut target_octave = self.target_octave.lock().unwrap();
+                ui.add(egui::Slider::new(&mut *target_octave, 0..=7).text("Target octave"));
+                drop(target_octave);
+                let playing = self.reference_tone.lock().unwrap().is_some();
+                ui.horizontal(|ui| {
+                    ui.label(format!("Tone octave shift: {:+}", self.tone_octave_shift));
+                    let mut changed = false;
+                    if ui.button("Octave down").clicked() && self.tone_octave_shift > -4 {
+                        self.tone_octave_shift -= 1;
+                        changed = true;
+                    }
+                    if ui.button("Octave up").clicked() && self.tone_octave_shift < 4 {
+                        self.tone_octave_shift += 1;
+                        changed = true;
+                    }
+                    if changed && playing {
+                        // Retune the running tone immediately.
+                        self.play_reference_tone();
+                    }
+                });
+                let button_label = if playing {
+                    "Stop reference"
+                } else {
+                    "Play reference"
+                };
+                if ui.button(button_label).clicked() {
+                    if playing {
+                        *self.reference_tone.lock().unwrap() = None;
+                    } else {
+                        self.play_reference_tone();
+                    }
+                }
+                if let Some(status) = &self.tone_status {
+                    ui.colored_label(egui::Color32::from_rgb(220, 60, 60), status);
+                }
+            }
+            let mut instrument_preset = self.instrument_preset.lock().unwrap();
+            let preset_label = match *instrument_preset {
+                Some(i) => INSTRUMENT_PRESETS[i].name,
+                None => "None (chromatic)",
+            };
+            egui::ComboBox::from_label("Instrument preset")
+                .selected_text(preset_label)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut *instrument_preset, None, "None (chromatic)");
+                    for (i, preset) in INSTRUMENT_PRESETS.iter().enumerate() {
+                        ui.selectable_value(&mut *instrument_preset, Some(i), preset.name);
+                    }
+                });
+            drop(instrument_preset);
+            // Spelling is a display step applied after transposition:
+            // transposing a respelled label like "B♭3" would find no
+            // match in NOTES and silently no-op.
+            let shift = TRANSPOSITIONS[self.transposition].1;
+            let spelling = *self.note_spelling.lock().unwrap();
+            let displayed_note = spell_note_label(&transpose_note_label(&note, shift), spelling);
+            self.draw_note_display(ui, &displayed_note, freq, cents);
+            ui.horizontal(|ui| {
+                let mut calibration = self.calibration.lock().unwrap();
+                ui.label(format!(
+                    "Calibration: {:+.1} cents",
+                    calibration_offset_cents(*calibration)
+                ));
+                if ui
+                    .button("Calibrate to 440 Hz")
+                    .on_hover_text("Play an exact 440 Hz reference, then click")
+                    .clicked()
+                    && freq > 0.0
+                {
+                    *calibration *= 440.0 / freq;
+                }
+                if ui.button("Reset").clicked() {
+                    *calibration = 1.0;
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut a4 = self.a4_reference.lock().unwrap();
+                ui.add(egui::Slider::new(&mut *a4, 390.0..=466.0).text("A4 reference"));
+                ui.add(
+                    egui::DragValue::new(&mut *a4)
+                        .speed(0.1)
+                        .range(390.0..=466.0)
+                        .suffix(" Hz"),
+                );
+                if ui.button("415").clicked() {
+                    *a4 = 415.0;
+                }
+                if ui.button("440").clicked() {
+                    *a4 = 440.0;
+                }
+                if ui.button("442").clicked() {
+                    *a4 = 442.0;
+                }
+            });
+            self.pitch_indicator = self.pitch_indicator.advance(cents);
+            match self.pitch_indicator {
+                PitchIndicator::Flat => {
+                    ui.colored_label(self.color_scheme.out_of_tune(), "↑ flat");
+                }
+                PitchIndicator::Sharp => {
+                    ui.colored_label(self.color_scheme.out_of_tune(), "↓ sharp");
+                }
+                PitchIndicator::InTune => {
+                    ui.colored_label(self.color_scheme.in_tune(), "in tune");
+                }
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Run self-test").clicked() {
+                    // Push synthetic tones through the whole detection
+                    // path; a failure here means the pipeline is broken,
+                    // not the microphone.
+                    let sample_rate = *self.sample_rate.lock().unwrap();
+                    let failed: Vec<String> = [110.0, 220.0, 440.0, 880.0]
+                        .iter()
+                        .filter(|&&frequency| !self_test(frequency, sample_rate))
+                        .map(|frequency| format!("{:.0} Hz", frequency))
+                        .collect();
+                    self.self_test_status = Some(if failed.is_empty() {
+                        "Self-test passed: 110/220/440/880 Hz all detected".to_string()
+                    } else {
+                        format!("Self-test FAILED at {}", failed.join(", "))
+                    });
+                }
+                if let Some(status) = &self.self_test_status {
+                    let color = if status.starts_with("Self-test passed") {
+                        self.color_scheme.in_tune()
+                    } else {
+                        self.color_scheme.out_of_tune()
+                    };
+                    ui.colored_label(color, status);
+                }
+            });
+            ui.checkbox(&mut self.piano_mode, "Piano mode (partial stretch)");
+            if self.piano_mode {
+                if let Some(b) = *self.inharmonicity.lock().unwrap() {
+                    // How sharp the 2nd partial sits relative to a pure
+                    // octave — the stretch a piano tuner would apply.
+                    let octave_stretch_cents = 600.0 * (1.0 + 4.0 * b).log2();
+                    ui.label(format!(
+                        "Inharmonicity B ≈ {:.6}; octave partial {:+.1} cents of pure",
+                        b, octave_stretch_cents
+                    ));
+                } else {
+                    ui.label("Inharmonicity: — (needs several clear partials)");
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.hold_enabled, "Hold");
+                if self.hold_enabled {
+                    if ui.button("Clear held").clicked() {
+                        self.held_reading = None;
+                    }
+                } else {
+                    self.held_reading = None;
+                }
+            });
+            if self.hold_enabled {
+                let confidence = *self.confidence.lock().unwrap();
+                // The gate blanks the note to an em dash; silence therefore
+                // never replaces (or clears) the captured reading.
+                let gated = note.trim().is_empty() || !note.starts_with(|c: char| c.is_ascii_alphabetic());
+                let more_confident = self
+                    .held_reading
+                    .as_ref()
+                    .is_none_or(|held| confidence > held.confidence);
+                if !gated && freq > 0.0 && more_confident {
+                    self.held_reading = Some(HeldReading {
+                        note: note.clone(),
+                        frequency: freq,
+                        cents,
+                        confidence,
+                    });
+                }
+                if let Some(held) = &self.held_reading {
+                    let spelling = *self.note_spelling.lock().unwrap();
+                    ui.label(format!(
+                        "Held: {} at {:.2} Hz ({:+.1} cents, confidence {:.0})",
+                        spell_note_label(&held.note, spelling),
+                        held.frequency,
+                        held.cents,
+                        held.confidence
+                    ));
+                }
+            }
+            if let Some(interval) = self.interval_display.lock().unwrap().clone() {
+                ui.label(format!("Interval: {}", interval));
+            }
+            let mut polyphonic = self.polyphonic.lock().unwrap();
+            ui.checkbox(&mut polyphonic, "Chord mode");
+            let chord_active = *polyphonic;
+            drop(polyphonic);
+            if chord_active {
+                let chord = self.chord_notes.lock().unwrap().clone();
+                if chord.is_empty() {
+                    ui.label("Chord: —");
+                } else {
+                    ui.label(format!("Chord: {}", chord.join(" ")));
+                }
+            }
+            self.detected_midi = frequency_to_midi(freq).map(|midi| midi.round() as i32);
+            match self.detected_midi {
+                Some(midi) => ui.label(format!("MIDI note: {}", midi)),
+                None => ui.label("MIDI note: —"),
+            };
+            let port_names = midi_port_names();
+            let midi_label = match self.midi_port_selection {
+                0 => "Off".to_string(),
+                1 => "Virtual port".to_string(),
+                selection => port_names
+                    .get(selection - 2)
+                    .cloned()
+                    .unwrap_or_else(|| "Off".to_string()),
+            };
+            let previous_selection = self.midi_port_selection;
+            egui::ComboBox::from_label("MIDI output")
+                .selected_text(midi_label)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.midi_port_selection, 0, "Off");
+                    #[cfg(unix)]
+                    ui.selectable_value(&mut self.midi_port_selection, 1, "Virtual port");
+                    for (i, name) in port_names.iter().enumerate() {
+                        ui.selectable_value(&mut self.midi_port_selection, i + 2, name);
+                    }
+                });
+            if self.midi_port_selection != previous_selection {
+                self.connect_midi();
+            }
+            if self.midi_port_selection != 0 {
+                let mut min_hold = *self.midi_min_hold_ms.lock().unwrap();
+                ui.add(egui::Slider::new(&mut min_hold, 50..=1000).text("MIDI hold (ms)"));
+                *self.midi_min_hold_ms.lock().unwrap() = min_hold;
+            }
+            if let Some(status) = &self.midi_status {
+                ui.colored_label(egui::Color32::from_rgb(220, 60, 60), status);
+            }
+            let confidence = *self.confidence.lock().unwrap();
+            // Normalize the peak/mean clarity ratio into a 0..1 bar.
+            ui.add(
+                egui::ProgressBar::new((confidence / 50.0).clamp(0.0, 1.0))
+                    .text(format!("Confidence {:.0}", confidence)),
+            );
+            let mut confidence_threshold = self.confidence_threshold.lock().unwrap();
+            ui.add(
+                egui::Slider::new(&mut *confidence_threshold, 1.0..=50.0)
+                    .text("Confidence threshold"),
+            );
+            drop(confidence_threshold);
+            let recording_active = self.recording.lock().unwrap().is_some();
+            let record_label = if recording_active {
+                "Stop recording"
+            } else {
+                "Record"
+            };
+            if ui.button(record_label).clicked() {
+                if recording_active {
+                    let take = self.recording.lock().unwrap().take();
+                    if let Some(samples) = take {
+                        if samples.is_empty() {
+                            self.save_status = Some("Recording captured no audio".to_string());
+                        } else {
+                            let timestamp = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .map(|d| d.as_secs())
+                                .unwrap_or(0);
+                            let path = format!("recording_{}.wav", timestamp);
+                            self.save_status =
+                                match write_wav(&path, &samples, *self.sample_rate.lock().unwrap()) {
+                                    Ok(()) => Some(format!("Saved {}", path)),
+                                    Err(err) => {
+                                        Some(format!("Failed to save recording: {}", err))
+                                    }
+                                };
+                        }
+                    }
+                } else {
+                    *self.recording.lock().unwrap() = Some(Vec::new());
+                    // A fresh take starts its statistics from zero.
+                    self.drift_stats.lock().unwrap().clear();
+                }
+            }
+            if ui.button("Save spectrum").clicked() {
+                let magnitudes = self.latest_spectrum.lock().unwrap().clone();
+                if magnitudes.is_empty() {
+                    self.save_status = Some("No spectrum captured yet".to_string());
+                } else {
+                    let bin_centers = bin_frequencies(*self.sample_rate.lock().unwrap(), self.window_size);
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0);
+                    let path = format!("spectrum_{}.png", timestamp);
+                    self.save_status =
+                        match plot_average_magnitudes_with_bins(&magnitudes, &bin_centers, &path) {
+                            Ok(()) => Some(format!("Saved {}", path)),
+                            Err(err) => Some(format!("Failed to save spectrum: {}", err)),
+                        };
+                }
+            }
+            if ui.button("Stop & Export pitch track").clicked() {
+                let records = std::mem::take(&mut *self.pitch_track.lock().unwrap());
+                if records.is_empty() {
+                    self.save_status = Some("No pitch track recorded yet".to_string());
+                } else {
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0);
+                    let path = format!("pitch_track_{}.csv", timestamp);
+                    let stats = *self.drift_stats.lock().unwrap();
+                    self.save_status = match write_pitch_track_csv(&path, &records, Some(&stats)) {
+                        Ok(()) => Some(format!("Saved {}", path)),
+                        Err(err) => Some(format!("Failed to save pitch track: {}", err)),
+                    };
+                }
+            }
+            if let Some(status) = &self.save_status {
+                ui.label(status);
+            }
+            let mut detection_method = self.detection_method.lock().unwrap();
+            egui::ComboBox::from_label("Detection method")
+                .selected_text(detection_method.name())
+                .show_ui(ui, |ui| {
+                    for option in DetectionMethod::ALL {
+                        ui.selectable_value(&mut *detection_method, option, option.name());
+                    }
+                });
+            if *detection_method == DetectionMethod::HarmonicProduct {
+                let mut harmonics = self.hps_harmonics.lock().unwrap();
+                ui.add(egui::Slider::new(&mut *harmonics, 2..=6).text("HPS harmonics"));
+            }
+            if *detection_method == DetectionMethod::Mpm {
+                let mut clarity = self.mpm_clarity_threshold.lock().unwrap();
+                ui.add(
+                    egui::Slider::new(&mut *clarity, 0.5..=0.95).text("MPM clarity threshold"),
+                );
+            }
+            let mut frame_aggregation = self.frame_aggregation.lock().unwrap();
+            egui::ComboBox::from_label("Frame aggregation")
+                .selected_text(frame_aggregation.name())
+                .show_ui(ui, |ui| {
+                    for option in FrameAggregation::ALL {
+                        ui.selectable_value(&mut *frame_aggregation, option, option.name());
+                    }
+                });
+            drop(frame_aggregation);
+            drop(detection_method);
+            let mut gate_threshold = self.gate_threshold_dbfs.lock().unwrap();
+            ui.add(
+                egui::Slider::new(&mut *gate_threshold, -80.0..=0.0).text("Noise gate (dBFS)"),
+            );
+            let mut band_min = self.band_min_hz.lock().unwrap();
+            let mut band_max = self.band_max_hz.lock().unwrap();
+            ui.add(egui::Slider::new(&mut *band_min, 20.0..=500.0).text("Detection min (Hz)"));
+            ui.add(egui::Slider::new(&mut *band_max, 500.0..=5000.0).text("Detection max (Hz)"));
+            drop(band_min);
+            drop(band_max);
+            drop(gate_threshold);
+            let mut mains_notch = self.mains_notch_hz.lock().unwrap();
+            let notch_label = |hz: f32| {
+                if hz > 0.0 {
+                    format!("{:.0} Hz", hz)
+                } else {
+                    "Off".to_string()
+                }
+            };
+            egui::ComboBox::from_label("Mains hum notch")
+                .selected_text(notch_label(*mains_notch))
+                .show_ui(ui, |ui| {
+                    for hz in [0.0f32, 50.0, 60.0] {
+                        ui.selectable_value(&mut *mains_notch, hz, notch_label(hz));
+                    }
+                });
+            drop(mains_notch);
+            let mut smoothing_frames = self.smoothing_frames.lock().unwrap();
+            ui.add(egui::Slider::new(&mut *smoothing_frames, 1..=15).text("Smoothing frames"));
+            drop(smoothing_frames);
+            let mut low_latency = self.low_latency.lock().unwrap();
+            ui.checkbox(&mut low_latency, "Low latency (less frequency resolution)");
+            let mut adaptive_window = self.adaptive_window.lock().unwrap();
+            ui.checkbox(&mut adaptive_window, "Adaptive window (long for bass, short for treble)");
+            drop(adaptive_window);
+            let mut whitening = self.whitening.lock().unwrap();
+            ui.checkbox(&mut whitening, "Spectral whitening (evens out timbre)");
+            drop(whitening);
+            let mut pre_emphasis_enabled = self.pre_emphasis.lock().unwrap();
+            ui.checkbox(&mut pre_emphasis_enabled, "Pre-emphasis (boosts treble detection)");
+            let show_coefficient = *pre_emphasis_enabled;
+            drop(pre_emphasis_enabled);
+            if show_coefficient {
+                let mut coefficient = self.pre_emphasis_coefficient.lock().unwrap();
+                ui.add(
+                    egui::Slider::new(&mut *coefficient, 0.5..=0.99)
+                        .text("Pre-emphasis coefficient"),
+                );
+                drop(coefficient);
+            }
+            let (window, hop) = effective_frame(self.window_size, self.hop_size, *low_latency);
+            drop(low_latency);
+            ui.label(format!(
+                "Approximate latency: {:.0} ms",
+                analysis_latency_ms(window, hop, *self.sample_rate.lock().unwrap())
+            ));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.dark_theme, "Dark theme");
+                ui.checkbox(&mut self.strobe_mode, "Strobe display");
+                egui::ComboBox::from_label("Color scheme")
+                    .selected_text(self.color_scheme.name())
+                    .show_ui(ui, |ui| {
+                        for option in ColorScheme::ALL {
+                            ui.selectable_value(&mut self.color_scheme, option, option.name());
+                        }
+                    });
+                // Large enough to read the note from a music stand.
+                ui.add(egui::Slider::new(&mut self.font_scale, 0.75..=2.0).text("UI scale"));
+            });
+            egui::ComboBox::from_label("Transposition")
+                .selected_text(TRANSPOSITIONS[self.transposition].0)
+                .show_ui(ui, |ui| {
+                    for (i, (name, _)) in TRANSPOSITIONS.iter().enumerate() {
+                        ui.selectable_value(&mut self.transposition, i, *name);
+                    }
+                });
+            ui.separator();
+            let mut metronome = *self.metronome.lock().unwrap();
+            ui.horizontal(|ui| {
+                let label = if metronome.running {
+                    "Stop metronome"
+                } else {
+                    "Start metronome"
+                };
+                if ui.button(label).clicked() {
+                    metronome.running = !metronome.running;
+                    if metronome.running && self.metronome_stream.is_none() {
+                        match start_metronome_stream(&self.metronome) {
+                            Ok(stream) => self.metronome_stream = Some(stream),
+                            Err(message) => {
+                                metronome.running = false;
+                                self.metronome_status = Some(message);
+                            }
+                        }
+                    }
+                }
+                ui.add(egui::Slider::new(&mut metronome.bpm, 40..=240).text("BPM"));
+                ui.add(
+                    egui::Slider::new(&mut metronome.beats_per_bar, 1..=8).text("Beats per bar"),
+                );
+            });
+            *self.metronome.lock().unwrap() = metronome;
+            if let Some(status) = &self.metronome_status {
+                ui.colored_label(egui::Color32::from_rgb(220, 60, 60), status);
+            }
+            ui.separator();
+            let mut edo_divisions = self.edo_divisions.lock().unwrap();
+            egui::ComboBox::from_label("Octave divisions")
+                .selected_text(format!("{}-EDO", *edo_divisions))
+                .show_ui(ui, |ui| {
+                    for divisions in [12usize, 19, 24, 31] {
+                        ui.selectable_value(
+                            &mut *edo_divisions,
+                            divisions,
+                            format!("{}-EDO", divisions),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("Temperament and tonic apply only in 12-EDO");
+            drop(edo_divisions);
+            ui.horizontal(|ui| {
+                ui.label("Scala scale:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.scala_path)
+                        .hint_text("path/to/scale.scl")
+                        .desired_width(180.0),
+                );
+                if ui.button("Load").clicked() {
+                    self.load_scala_scale();
+                }
+                if self.scala_scale.lock().unwrap().is_some() && ui.button("Clear").clicked() {
+                    *self.scala_scale.lock().unwrap() = None;
+                    self.scala_status = None;
+                }
+            });
+            if let Some(status) = &self.scala_status {
+                let color = if status.starts_with("Loaded") {
+                    self.color_scheme.in_tune()
+                } else {
+                    egui::Color32::from_rgb(220, 60, 60)
+                };
+                ui.colored_label(color, status);
+            }
+            let mut temperament = self.temperament.lock().unwrap();
+            egui::ComboBox::from_label("Temperament")
+                .selected_text(temperament.name())
+                .show_ui(ui, |ui| {
+                    for option in Temperament::ALL {
+                        ui.selectable_value(&mut *temperament, option, option.name());
+                    }
+                });
+            let mut note_spelling = self.note_spelling.lock().unwrap();
+            egui::ComboBox::from_label("Accidentals")
+                .selected_text(note_spelling.name())
+                .show_ui(ui, |ui| {
+                    for option in NoteSpelling::ALL {
+                        ui.selectable_value(&mut *note_spelling, option, option.name());
+                    }
+                });
+            drop(note_spelling);
+            let mut tonic = self.tonic.lock().unwrap();
+            egui::ComboBox::from_label("Tonic")
+                .selected_text(NOTES[*tonic].0)
+                .show_ui(ui, |ui| {
+                    for (i, (name, _)) in NOTES.iter().enumerate() {
+                        ui.selectable_value(&mut *tonic, i, *name);
+                    }
+                });
         });
+
+        // Persist settings as soon as any of them change.
+        let current_settings = self.snapshot_settings();
+        if current_settings != self.last_saved_settings {
+            if let Some(path) = settings_path()
+                && let Err(err) = save_settings(&path, &current_settings)
+            {
+                warn!("Could not save settings: {}", err);
+            }
+            self.last_saved_settings = current_settings;
+        }
     }
 }
 
-static NOTES: [(&str, f32); 12] = [
-    ("C", 261.63),
-    ("C#", 277.18),
-    ("D", 293.66),
-    ("D#", 311.13),
-    ("E", 329.63),
-    ("F", 349.23),
-    ("F#", 369.99),
-    ("G", 392.00),
-    ("G#", 415.30),
-    ("A", 440.00),
-    ("A#", 466.16),
-    ("B", 493.88),
-];
+/// Per-frame stdout format for the analyze mode. Plain keeps the human
+/// summary; the other two emit one machine-readable line per track frame
+/// so the output can be piped into other tools.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum OutputFormat {
+    Plain,
+    Json,
+    Csv,
+}
+
+/// One pitch-track frame as a single-line JSON object.
+fn format_frame_json(frame: &PitchFrame) -> String {
+    // Note names only contain ASCII letters, digits and '#', so no JSON
+    // string escaping is needed.
+    let note = match &frame.note {
+        Some(note) => format!("\"{}\"", note),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"time\":{:.3},\"freq\":{:.2},\"note\":{},\"cents\":{:.1},\"confidence\":{:.3}}}",
+        frame.time, frame.frequency, note, frame.cents, frame.confidence
+    )
+}
+
+/// One pitch-track frame as a CSV row matching the `time,freq,note,cents,
+/// confidence` header; the note column is empty outside the note range.
+fn format_frame_csv(frame: &PitchFrame) -> String {
+    format!(
+        "{:.3},{:.2},{},{:.1},{:.3}",
+        frame.time,
+        frame.frequency,
+        frame.note.as_deref().unwrap_or(""),
+        frame.cents,
+        frame.confidence
+    )
+}
+
+/// Offline analysis of a WAV file instead of live tuning.
+struct AnalyzeArgs {
+    input: String,
+    spectrogram: Option<String>,
+    format: OutputFormat,
+    // Treat `input` as a directory and analyze every audio file in it.
+    batch: bool,
+    // Optional time region in seconds; validated against the duration
+    // once the file is decoded.
+    start: Option<f32>,
+    end: Option<f32>,
+}
+
+struct CliArgs {
+    window_size: usize,
+    hop_size: usize,
+    headless: bool,
+    serve: Option<u16>,
+    analyze: Option<AnalyzeArgs>,
+}
+
+fn parse_cli_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut window_size = 4096usize;
+    let mut hop_size = None;
+    let mut analyze_input = None;
+    let mut spectrogram = None;
+    let mut headless = false;
+    let mut serve = None;
+    let mut format = OutputFormat::Plain;
+    let mut batch = false;
+    let mut start = None;
+    let mut end = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "analyze" => {
+                let value = iter
+                    .next()
+                    .ok_or("analyze requires a WAV file path".to_string())?;
+                analyze_input = Some(value.clone());
+            }
+            "--headless" => headless = true,
+            "--batch" => batch = true,
+            "--start" => {
+                let value = iter.next().ok_or("--start requires seconds".to_string())?;
+                start = Some(
+                    value
+                        .parse::<f32>()
+                        .map_err(|_| format!("invalid start time '{}'", value))?,
+                );
+            }
+            "--end" => {
+                let value = iter.next().ok_or("--end requires seconds".to_string())?;
+                end = Some(
+                    value
+                        .parse::<f32>()
+                        .map_err(|_| format!("invalid end time '{}'", value))?,
+                );
+            }
+            "--serve" => {
+                let value = iter.next().ok_or("--serve requires a port".to_string())?;
+                serve = Some(
+                    value
+                        .parse::<u16>()
+                        .map_err(|_| format!("invalid port '{}'", value))?,
+                );
+            }
+            "--spectrogram" => {
+                let value = iter
+                    .next()
+                    .ok_or("--spectrogram requires an output path".to_string())?;
+                spectrogram = Some(value.clone());
+            }
+            "--format" => {
+                let value = iter.next().ok_or("--format requires a value".to_string())?;
+                format = match value.as_str() {
+                    "plain" => OutputFormat::Plain,
+                    "json" => OutputFormat::Json,
+                    "csv" => OutputFormat::Csv,
+                    other => {
+                        return Err(format!(
+                            "unknown format '{}' (expected plain, json or csv)",
+                            other
+                        ));
+                    }
+                };
+            }
+            "--window-size" => {
+                let value = iter
+                    .next()
+                    .ok_or("--window-size requires a value".to_string())?;
+                window_size = value
+                    .parse()
+                    .map_err(|_| format!("invalid window size '{}'", value))?;
+            }
+            "--hop-size" => {
+                let value = iter.next().ok_or("--hop-size requires a value".to_string())?;
+                hop_size = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid hop size '{}'", value))?,
+                );
+            }
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+    let hop_size = hop_size.unwrap_or(window_size / 2);
+    if window_size == 0 || !window_size.is_power_of_two() {
+        return Err(format!(
+            "window size must be a power of two, got {}",
+            window_size
+        ));
+    }
+    if hop_size == 0 || hop_size > window_size {
+        return Err(format!(
+            "hop size must be between 1 and the window size ({}), got {}",
+            window_size, hop_size
+        ));
+    }
+    if spectrogram.is_some() && analyze_input.is_none() {
+        return Err("--spectrogram only applies to the analyze mode".to_string());
+    }
+    if batch && analyze_input.is_none() {
+        return Err("--batch only applies to the analyze mode".to_string());
+    }
+    if (start.is_some() || end.is_some()) && analyze_input.is_none() {
+        return Err("--start/--end only apply to the analyze mode".to_string());
+    }
+    if (start.is_some() || end.is_some()) && batch {
+        return Err("--start/--end cannot be combined with --batch".to_string());
+    }
+    if batch && spectrogram.is_some() {
+        return Err("--spectrogram cannot be combined with --batch".to_string());
+    }
+    if batch && format != OutputFormat::Plain {
+        return Err(
+            "--format cannot be combined with --batch (it writes per-file CSVs)".to_string(),
+        );
+    }
+    if headless && analyze_input.is_none() {
+        return Err("--headless requires the analyze mode".to_string());
+    }
+    if format != OutputFormat::Plain && analyze_input.is_none() {
+        return Err("--format only applies to the analyze mode".to_string());
+    }
+    if serve.is_some() && !cfg!(feature = "serve") {
+        return Err(
+            "this build does not include the serve feature; rebuild with --features serve"
+                .to_string(),
+        );
+    }
+    Ok(CliArgs {
+        window_size,
+        hop_size,
+        headless,
+        serve,
+        analyze: analyze_input.map(|input| AnalyzeArgs {
+            input,
+            spectrogram,
+            format,
+            batch,
+            start,
+            end,
+        }),
+    })
+}
+
+/// Sample range for a `--start`/`--end` selection, validated against the
+/// decoded duration so out-of-range offsets fail with a clear message
+/// instead of silently analyzing the wrong region.
+fn time_region(
+    samples_len: usize,
+    sample_rate: usize,
+    start: Option<f32>,
+    end: Option<f32>,
+) -> Result<std::ops::Range<usize>, String> {
+    let duration = samples_len as f32 / sample_rate as f32;
+    let start_seconds = start.unwrap_or(0.0);
+    let end_seconds = end.unwrap_or(duration);
+    if start_seconds < 0.0 || start_seconds > duration {
+        return Err(format!(
+            "--start {:.2} s is outside the file ({:.2} s long)",
+            start_seconds, duration
+        ));
+    }
+    if end_seconds > duration {
+        return Err(format!(
+            "--end {:.2} s is beyond the end of the file ({:.2} s long)",
+            end_seconds, duration
+        ));
+    }
+    if start_seconds >= end_seconds {
+        return Err(format!(
+            "--start must come before --end ({:.2} s >= {:.2} s)",
+            start_seconds, end_seconds
+        ));
+    }
+    let first = (start_seconds * sample_rate as f32).round() as usize;
+    let last = ((end_seconds * sample_rate as f32).round() as usize).min(samples_len);
+    Ok(first..last)
+}
+
+/// Run the detection pipeline over a WAV file and report the result on
+/// stdout, optionally writing a spectrogram image. In headless mode only
+/// the detection result is printed, keeping stdout easy for scripts to
+/// parse.
+fn run_analyze(
+    analyze: &AnalyzeArgs,
+    window_size: usize,
+    hop_size: usize,
+    headless: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (sample_rate, samples) = read_audio(&analyze.input)?;
+    let region = time_region(samples.len(), sample_rate, analyze.start, analyze.end)?;
+    let samples = samples[region].to_vec();
+    check_buffer_length(samples.len(), window_size)
+        .map_err(|message| format!("'{}': {}", analyze.input, message))?;
+    if is_clipping(&samples) {
+        // stderr so machine-readable stdout formats stay clean.
+        eprintln!(
+            "Warning: '{}' is clipping — reduce gain; harmonic distortion may skew detection",
+            analyze.input
+        );
+    }
+    let frames = compute_short_time_fourier_transform(&samples, window_size, hop_size);
+    if let Some(path) = &analyze.spectrogram {
+        plot_spectrogram(&frames, sample_rate, path)?;
+        if !headless && analyze.format == OutputFormat::Plain {
+            println!("Wrote spectrogram to {}", path);
+        }
+    }
+    if analyze.format != OutputFormat::Plain {
+        // Machine formats replace the human summary entirely: one line per
+        // track frame and nothing else on stdout.
+        let track = analyze_pitch_track(&samples, sample_rate, window_size, hop_size);
+        if analyze.format == OutputFormat::Csv {
+            println!("time,freq,note,cents,confidence");
+        }
+        for frame in &track {
+            match analyze.format {
+                OutputFormat::Json => println!("{}", format_frame_json(frame)),
+                OutputFormat::Csv => println!("{}", format_frame_csv(frame)),
+                OutputFormat::Plain => unreachable!(),
+            }
+        }
+        return Ok(());
+    }
+    match detect_pitch(&samples, sample_rate, window_size, hop_size) {
+        Some(freq) => {
+            match frequency_to_note(freq, Temperament::Equal, 0) {
+                Some((note, note_freq)) => println!(
+                    "Detected {:.2} Hz: {} ({:+.1} cents)",
+                    freq,
+                    note,
+                    cents_offset(freq, note_freq)
+                ),
+                None => println!("Detected {:.2} Hz (outside note range)", freq),
+            }
+        }
+        None => println!("No pitch detected"),
+    }
+    // The segment and key report is extra context for a human reader;
+    // headless keeps stdout to the detection result alone.
+    if headless {
+        return Ok(());
+    }
+    let track = analyze_pitch_track(&samples, sample_rate, window_size, hop_size);
+    // Split the track at detected onsets and report one line per note.
+    let onsets = detect_onsets(&frames, sample_rate, hop_size);
+    let total_seconds = samples.len() as f32 / sample_rate as f32;
+    let mut boundaries = vec![0.0f32];
+    boundaries.extend(&onsets);
+    boundaries.push(total_seconds);
+    println!("Note segments:");
+    for pair in boundaries.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let mut counts: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+        for frame in &track {
+            if frame.time >= start
+                && frame.time < end
+                && let Some(note) = &frame.note
+            {
+                *counts.entry(note.as_str()).or_insert(0) += 1;
+            }
+        }
+        let dominant = counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(note, _)| note)
+            .unwrap_or("—");
+        println!(
+            "  {:.2}-{:.2} s: {} ({:.2} s)",
+            start,
+            end,
+            dominant,
+            end - start
+        );
+    }
+    match estimate_key(&track) {
+        Some((tonic, mode)) => println!("Estimated key: {} {}", tonic, mode),
+        None => println!("Estimated key: unknown"),
+    }
+    Ok(())
+}
+
+/// Analyze every audio file in a directory: each file gets a pitch-track
+/// CSV written next to it, named after the input, and a one-line summary
+/// with the estimated key goes to stdout. A file that fails is reported
+/// on stderr and the rest of the batch continues; any failure turns into
+/// an error at the end so scripts see a nonzero exit status.
+fn run_batch_analyze(
+    analyze: &AnalyzeArgs,
+    window_size: usize,
+    hop_size: usize,
+    headless: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&analyze.input)
+        .map_err(|error| format!("'{}': {}", analyze.input, error))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            // Same formats `read_audio` handles (wav natively, the rest
+            // via symphonia); everything else (notes, the CSVs from a
+            // previous run, ...) is silently skipped.
+            matches!(
+                path.extension()
+                    .and_then(|e| e.to_str())
+                    .map(|e| e.to_ascii_lowercase())
+                    .as_deref(),
+                Some("wav") | Some("mp3") | Some("flac") | Some("ogg")
+            )
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no audio files found in '{}'", analyze.input).into());
+    }
+    let mut failures = 0usize;
+    for path in &files {
+        let result = (|| -> Result<(usize, String), Box<dyn Error>> {
+            let input = path.to_str().ok_or("non-UTF-8 file name")?;
+            let (sample_rate, samples) = read_audio(input)?;
+            check_buffer_length(samples.len(), window_size)?;
+            let track = analyze_pitch_track(&samples, sample_rate, window_size, hop_size);
+            let records: Vec<PitchRecord> = track
+                .iter()
+                .map(|frame| PitchRecord {
+                    timestamp_seconds: frame.time,
+                    frequency: frame.frequency,
+                    note: frame.note.clone().unwrap_or_else(|| "—".to_string()),
+                    cents_offset: frame.cents,
+                })
+                .collect();
+            let csv_path = path.with_extension("csv");
+            write_pitch_track_csv(
+                csv_path.to_str().ok_or("non-UTF-8 file name")?,
+                &records,
+                None,
+            )?;
+            let key = match estimate_key(&track) {
+                Some((tonic, mode)) => format!("{} {}", tonic, mode),
+                None => "unknown".to_string(),
+            };
+            Ok((records.len(), key))
+        })();
+        match result {
+            Ok((frames, key)) => {
+                if !headless {
+                    println!(
+                        "{}: {} frames, estimated key {}, wrote {}",
+                        path.display(),
+                        frames,
+                        key,
+                        path.with_extension("csv").display()
+                    );
+                }
+            }
+            Err(error) => {
+                failures += 1;
+                eprintln!("Error: '{}': {}", path.display(), error);
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(format!("{} of {} files failed", failures, files.len()).into());
+    }
+    Ok(())
+}
+
+/// Open the default output device and start a stream synthesizing a sine
+/// at whatever frequency the shared cell holds (silence when None). A
+/// short gain ramp fades the tone in and out so starts and stops don't
+/// click.
+fn start_reference_tone_stream(
+    tone_frequency: &Arc<Mutex<Option<f32>>>,
+) -> Result<cpal::Stream, String> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| "No audio output device available".to_string())?;
+    let config = device
+        .default_output_config()
+        .map_err(|e| format!("Could not read the output device configuration: {}", e))?;
+    if config.sample_format() != cpal::SampleFormat::F32 {
+        return Err(format!(
+            "Unsupported output sample format: {:?}",
+            config.sample_format()
+        ));
+    }
+    let sample_rate = config.sample_rate().0 as f32;
+    let channels = config.channels() as usize;
+    let tone = tone_frequency.clone();
+    let mut phase = 0.0f32;
+    let mut gain = 0.0f32;
+    let stream = device
+        .build_output_stream(
+            &config.into(),
+            move |data: &mut [f32], _| {
+                let (freq, target_gain) = match *lock_or_recover(&tone) {
+                    Some(freq) => (freq, 0.25f32),
+                    None => (440.0, 0.0),
+                };
+                // One-pole ramp of roughly 5 ms toward the target gain.
+                let ramp = 1.0 / (0.005 * sample_rate);
+                for frame in data.chunks_mut(channels) {
+                    gain += (target_gain - gain) * ramp;
+                    let value = (phase * 2.0 * PI).sin() * gain;
+                    phase = (phase + freq / sample_rate).fract();
+                    for sample in frame.iter_mut() {
+                        *sample = value;
+                    }
+                }
+            },
+            move |err| error!("Output stream error: {:?}", err),
+            None,
+        )
+        .map_err(|e| format!("Could not open the output stream: {}", e))?;
+    stream
+        .play()
+        .map_err(|e| format!("Could not start the output stream: {}", e))?;
+    Ok(stream)
+}
+
+/// Shared metronome controls read by its output stream callback.
+#[derive(Clone, Copy)]
+struct MetronomeSettings {
+    running: bool,
+    bpm: u32,
+    beats_per_bar: u32,
+}
+
+/// Open the default output device and start a stream clicking at the shared
+/// BPM, accenting the first beat of each bar with a higher pitch. Click
+/// times come from a per-sample countdown rather than thread sleeps, so the
+/// pulse stays accurate over long sessions.
+fn start_metronome_stream(
+    settings: &Arc<Mutex<MetronomeSettings>>,
+) -> Result<cpal::Stream, String> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| "No audio output device available".to_string())?;
+    let config = device
+        .default_output_config()
+        .map_err(|e| format!("Could not read the output device configuration: {}", e))?;
+    if config.sample_format() != cpal::SampleFormat::F32 {
+        return Err(format!(
+            "Unsupported output sample format: {:?}",
+            config.sample_format()
+        ));
+    }
+    let sample_rate = config.sample_rate().0 as f32;
+    let channels = config.channels() as usize;
+    let settings = settings.clone();
+    let click_samples = (sample_rate * 0.03) as usize;
+    let mut samples_until_beat = 0.0f64;
+    let mut beat_in_bar = 0u32;
+    let mut click_freq = 2000.0f32;
+    // Samples since the current click started; past the end means silence.
+    let mut click_pos = usize::MAX;
+    let stream = device
+        .build_output_stream(
+            &config.into(),
+            move |data: &mut [f32], _| {
+                let settings = *lock_or_recover(&settings);
+                let samples_per_beat = f64::from(sample_rate) * 60.0 / f64::from(settings.bpm);
+                for frame in data.chunks_mut(channels) {
+                    if settings.running {
+                        if samples_until_beat <= 0.0 {
+                            samples_until_beat += samples_per_beat;
+                            click_freq = if beat_in_bar == 0 { 3000.0 } else { 2000.0 };
+                            click_pos = 0;
+                            beat_in_bar = (beat_in_bar + 1) % settings.beats_per_bar.max(1);
+                        }
+                        samples_until_beat -= 1.0;
+                    } else {
+                        samples_until_beat = 0.0;
+                        beat_in_bar = 0;
+                    }
+                    let mut value = 0.0;
+                    if click_pos < click_samples {
+                        let t = click_pos as f32 / sample_rate;
+                        value = (2.0 * PI * click_freq * t).sin() * (-t / 0.01).exp() * 0.5;
+                        click_pos += 1;
+                    }
+                    for sample in frame.iter_mut() {
+                        *sample = value;
+                    }
+                }
+            },
+            move |err| error!("Output stream error: {:?}", err),
+            None,
+        )
+        .map_err(|e| format!("Could not open the output stream: {}", e))?;
+    stream
+        .play()
+        .map_err(|e| format!("Could not start the output stream: {}", e))?;
+    Ok(stream)
+}
+
+/// Open the default input device and start a capture stream feeding the
+/// shared buffer, returning the stream and its sample rate. Every failure
+/// is a descriptive message the GUI can display, so a machine with no
+/// microphone (or denied permissions) gets an explanation, not a panic.
+/// Reference-tone frequency for a target note shifted by whole octaves
+/// from its selected octave. Frequencies below the audible range or above
+/// the Nyquist limit of the analysis rate are refused with a message
+/// rather than synthesizing something inaudible or aliased.
+fn reference_tone_frequency(
+    base: f32,
+    octave: i32,
+    octave_shift: i32,
+    sample_rate: usize,
+) -> Result<f32, String> {
+    let freq = base * 2f32.powi(octave - 4 + octave_shift);
+    if freq < 20.0 {
+        Err(format!("{:.1} Hz is below the audible range", freq))
+    } else if freq > sample_rate as f32 / 2.0 {
+        Err(format!(
+            "{:.1} Hz is above the Nyquist frequency at {} Hz",
+            freq, sample_rate
+        ))
+    } else {
+        Ok(freq)
+    }
+}
+
+/// Human label for the channel-selection combo; index shown one-based.
+fn channel_label(selection: ChannelSelection) -> String {
+    match selection {
+        ChannelSelection::Mix => "Mix".to_string(),
+        ChannelSelection::Left => "Left".to_string(),
+        ChannelSelection::Right => "Right".to_string(),
+        ChannelSelection::Index(index) => format!("Channel {}", index + 1),
+    }
+}
+
+/// Serve the current reading as JSON over local HTTP for overlays and
+/// other tools (enabled with `--serve PORT` on a build with the `serve`
+/// feature). Each `GET /` returns one snapshot of the shared state:
+///
+/// ```text
+/// {"note":"A4","freq":440.00,"cents":-2.1,"confidence":12.345}
+/// ```
+///
+/// The server runs on its own thread and only ever locks the four reading
+/// cells briefly per request, so clients connecting, polling, or vanishing
+/// never touch the audio or analysis threads.
+#[cfg(feature = "serve")]
+fn start_serve_thread(
+    port: u16,
+    detected_note: Arc<Mutex<String>>,
+    detected_freq: Arc<Mutex<f32>>,
+    detected_cents: Arc<Mutex<f32>>,
+    confidence: Arc<Mutex<f32>>,
+) -> Result<(), String> {
+    let server = tiny_http::Server::http(("127.0.0.1", port))
+        .map_err(|e| format!("Could not bind 127.0.0.1:{}: {}", port, e))?;
+    info!("Serving readings on http://127.0.0.1:{}/", port);
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let body = format!(
+                "{{\"note\":\"{}\",\"freq\":{:.2},\"cents\":{:.1},\"confidence\":{:.3}}}",
+                lock_or_recover(&detected_note),
+                *lock_or_recover(&detected_freq),
+                *lock_or_recover(&detected_cents),
+                *lock_or_recover(&confidence),
+            );
+            let response = tiny_http::Response::from_string(body).with_header(
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                    .expect("static header is valid"),
+            );
+            // A client that hung up mid-response is its own problem.
+            let _ = request.respond(response);
+        }
+    });
+    Ok(())
+}
+
+fn start_input_stream(
+    audio_data: &Arc<Mutex<Vec<f32>>>,
+    recording: &Arc<Mutex<Option<Vec<f32>>>>,
+    input_level: &Arc<Mutex<InputLevel>>,
+    channel_selection: &Arc<Mutex<ChannelSelection>>,
+    audio_ready: &Arc<Condvar>,
+    internal_sample_rate: usize,
+) -> Result<(cpal::Stream, usize), String> {
+    let host = cpal::default_host();
+    let device = host.default_input_device().ok_or_else(|| {
+        "No audio input device available; check that a microphone is connected and permitted"
+            .to_string()
+    })?;
+    info!(
+        "Using input device: {}",
+        device.name().unwrap_or_else(|_| "unknown".to_string())
+    );
+    let config = device
+        .default_input_config()
+        .map_err(|e| format!("Could not read the input device configuration: {}", e))?;
+    let device_rate = config.sample_rate().0 as usize;
+    if device_rate == 0 {
+        return Err("Input device reported a sample rate of 0".to_string());
+    }
+    // Everything downstream of the callback works at the canonical rate;
+    // 0 disables resampling and keeps the device's native rate.
+    let sample_rate = if internal_sample_rate > 0 {
+        internal_sample_rate
+    } else {
+        device_rate
+    };
+    if sample_rate != device_rate {
+        info!(
+            "Resampling device rate {} Hz to internal rate {} Hz",
+            device_rate, sample_rate
+        );
+    }
+    let channels = config.channels() as usize;
+    if let ChannelSelection::Index(index) = *lock_or_recover(channel_selection)
+        && index >= channels
+    {
+        warn!(
+            "Channel {} is beyond the device's {} channels; using the mix",
+            index + 1,
+            channels
+        );
+    }
+    let mut conversion = InputConversion {
+        channels,
+        resampler: StreamResampler::new(device_rate, sample_rate),
+        max_buffer_samples: sample_rate * MAX_BUFFER_SECONDS,
+    };
+    let audio_data_clone = audio_data.clone();
+    let audio_ready_clone = audio_ready.clone();
+    let channel_selection_clone = channel_selection.clone();
+    let recording_clone = recording.clone();
+    let input_level_clone = input_level.clone();
+    let sample_format = config.sample_format();
+    let stream_config: cpal::StreamConfig = config.into();
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &stream_config,
+            move |data: &[f32], _| {
+                push_input_samples(
+                    &audio_data_clone,
+                    &recording_clone,
+                    &input_level_clone,
+                    &channel_selection_clone,
+                    &audio_ready_clone,
+                    &mut conversion,
+                    data,
+                )
+            },
+            move |err| error!("Stream error: {:?}", err),
+            None,
+        ),
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &stream_config,
+            move |data: &[i16], _| {
+                let converted: Vec<f32> = data.iter().map(|&s| i16_sample_to_f32(s)).collect();
+                push_input_samples(
+                    &audio_data_clone,
+                    &recording_clone,
+                    &input_level_clone,
+                    &channel_selection_clone,
+                    &audio_ready_clone,
+                    &mut conversion,
+                    &converted,
+                );
+            },
+            move |err| error!("Stream error: {:?}", err),
+            None,
+        ),
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &stream_config,
+            move |data: &[u16], _| {
+                let converted: Vec<f32> = data.iter().map(|&s| u16_sample_to_f32(s)).collect();
+                push_input_samples(
+                    &audio_data_clone,
+                    &recording_clone,
+                    &input_level_clone,
+                    &channel_selection_clone,
+                    &audio_ready_clone,
+                    &mut conversion,
+                    &converted,
+                );
+            },
+            move |err| error!("Stream error: {:?}", err),
+            None,
+        ),
+        other => return Err(format!("Unsupported sample format: {:?}", other)),
+    }
+    .map_err(|e| format!("Could not open the input stream: {}", e))?;
+    stream
+        .play()
+        .map_err(|e| format!("Could not start the input stream: {}", e))?;
+    Ok((stream, sample_rate))
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // Quiet by default; RUST_LOG=rustique=debug shows per-frame detection.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli_args = match parse_cli_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!(
+                "Usage: rustique [--window-size N] [--hop-size N] [--headless] [--serve PORT] [analyze FILE [--start S] [--end S] [--spectrogram PNG] [--format plain|json|csv] | analyze DIR --batch]"
+            );
+            std::process::exit(1);
+        }
+    };
+    // Analysis never touches egui/eframe, so scripts and CI can run it on
+    // machines with no display; exit explicitly with a clean status code.
+    if let Some(analyze) = &cli_args.analyze {
+        let result = if analyze.batch {
+            run_batch_analyze(
+                analyze,
+                cli_args.window_size,
+                cli_args.hop_size,
+                cli_args.headless,
+            )
+        } else {
+            run_analyze(
+                analyze,
+                cli_args.window_size,
+                cli_args.hop_size,
+                cli_args.headless,
+            )
+        };
+        match result {
+            Ok(()) => std::process::exit(0),
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+    let settings = settings_path()
+        .map(|path| load_settings(&path))
+        .unwrap_or_default();
     let detected_note = Arc::new(Mutex::new("A4".to_string()));
     let detected_freq = Arc::new(Mutex::new(440.0_f32));
+    let temperament = Arc::new(Mutex::new(settings.temperament));
+    let note_spelling = Arc::new(Mutex::new(settings.note_spelling));
+    let tonic = Arc::new(Mutex::new(settings.tonic));
+    let gate_threshold_dbfs = Arc::new(Mutex::new(settings.gate_threshold_dbfs));
+    let detection_method = Arc::new(Mutex::new(settings.detection_method));
+    let frame_aggregation = Arc::new(Mutex::new(settings.frame_aggregation));
+    let frame_aggregation_clone = frame_aggregation.clone();
+    let band_min_hz = Arc::new(Mutex::new(settings.band_min_hz));
+    let band_min_clone = band_min_hz.clone();
+    let band_max_hz = Arc::new(Mutex::new(settings.band_max_hz));
+    let band_max_clone = band_max_hz.clone();
+    let mains_notch_hz = Arc::new(Mutex::new(settings.mains_notch_hz));
+    let mains_notch_clone = mains_notch_hz.clone();
+    let tuner_mode = Arc::new(Mutex::new(TunerMode::Chromatic));
+    let tuner_mode_clone = tuner_mode.clone();
+    let instrument_preset = Arc::new(Mutex::new(None::<usize>));
+    let instrument_preset_clone = instrument_preset.clone();
+    let target_note_index = Arc::new(Mutex::new(9usize));
+    // Seeded with A4, the usual starting point for a group tune-up.
+    let ensemble_targets = Arc::new(Mutex::new(vec![(9usize, 4i32)]));
+    let ensemble_targets_clone = ensemble_targets.clone();
+    let target_note_index_clone = target_note_index.clone();
+    let target_octave = Arc::new(Mutex::new(4i32));
+    let target_octave_clone = target_octave.clone();
+    let smoothing_frames = Arc::new(Mutex::new(settings.smoothing_frames));
+    let smoothing_frames_clone = smoothing_frames.clone();
+    let low_latency = Arc::new(Mutex::new(settings.low_latency));
+    let low_latency_clone = low_latency.clone();
+    let adaptive_window = Arc::new(Mutex::new(settings.adaptive_window));
+    let adaptive_window_clone = adaptive_window.clone();
+    let whitening = Arc::new(Mutex::new(settings.whitening));
+    let whitening_clone = whitening.clone();
+    let pre_emphasis_enabled = Arc::new(Mutex::new(settings.pre_emphasis));
+    let pre_emphasis_enabled_clone = pre_emphasis_enabled.clone();
+    let pre_emphasis_coefficient = Arc::new(Mutex::new(settings.pre_emphasis_coefficient));
+    let pre_emphasis_coefficient_clone = pre_emphasis_coefficient.clone();
+    let mpm_clarity_threshold = Arc::new(Mutex::new(settings.mpm_clarity_threshold));
+    let mpm_clarity_threshold_clone = mpm_clarity_threshold.clone();
+    let hps_harmonics = Arc::new(Mutex::new(settings.hps_harmonics));
+    let hps_harmonics_clone = hps_harmonics.clone();
+    let spectrum_smoothing = Arc::new(Mutex::new(settings.spectrum_smoothing));
+    let spectrum_smoothing_clone = spectrum_smoothing.clone();
+    let edo_divisions = Arc::new(Mutex::new(settings.edo_divisions));
+    let edo_divisions_clone = edo_divisions.clone();
+    let confidence = Arc::new(Mutex::new(0.0_f32));
+    let confidence_clone = confidence.clone();
+    let inharmonicity = Arc::new(Mutex::new(None));
+    let inharmonicity_clone = inharmonicity.clone();
+    let confidence_threshold = Arc::new(Mutex::new(settings.confidence_threshold));
+    let confidence_threshold_clone = confidence_threshold.clone();
+    let midi_connection: Arc<Mutex<Option<MidiOutputConnection>>> = Arc::new(Mutex::new(None));
+    let midi_connection_clone = midi_connection.clone();
+    let midi_min_hold_ms = Arc::new(Mutex::new(100u64));
+    let midi_min_hold_clone = midi_min_hold_ms.clone();
+    let detected_cents = Arc::new(Mutex::new(0.0_f32));
+    let cents_clone = detected_cents.clone();
+    if let Some(port) = cli_args.serve {
+        #[cfg(feature = "serve")]
+        if let Err(message) = start_serve_thread(
+            port,
+            detected_note.clone(),
+            detected_freq.clone(),
+            detected_cents.clone(),
+            confidence.clone(),
+        ) {
+            warn!("Could not start the serve endpoint: {}", message);
+        }
+        #[cfg(not(feature = "serve"))]
+        let _ = port;
+    }
+    let polyphonic = Arc::new(Mutex::new(false));
+    let polyphonic_clone = polyphonic.clone();
+    let calibration = Arc::new(Mutex::new(settings.calibration));
+    let calibration_clone = calibration.clone();
+    let a4_reference = Arc::new(Mutex::new(settings.a4_reference));
+    let a4_reference_clone = a4_reference.clone();
+    let scala_scale: Arc<Mutex<Option<(ScalaScale, f32)>>> = Arc::new(Mutex::new(None));
+    let scala_scale_clone = scala_scale.clone();
+    let chord_notes = Arc::new(Mutex::new(Vec::new()));
+    let chord_notes_clone = chord_notes.clone();
+    let interval_display = Arc::new(Mutex::new(None::<String>));
+    let interval_clone = interval_display.clone();
+    let latest_spectrum = Arc::new(Mutex::new(Vec::<f32>::new()));
+    let spectrum_clone = latest_spectrum.clone();
+    let pitch_track = Arc::new(Mutex::new(Vec::<PitchRecord>::new()));
+    let pitch_track_clone = pitch_track.clone();
+    let drift_stats = Arc::new(Mutex::new(DriftStats::new()));
+    let drift_stats_clone = drift_stats.clone();
     let note_clone = detected_note.clone();
     let freq_clone = detected_freq.clone();
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .expect("No input device available");
-    println!("Using input device: {}", device.name()?);
-    let config = device.default_input_config()?;
-    let sample_rate = config.sample_rate().0 as usize;
-    let window_size = 4096;
-    let hop_size = window_size / 2;
+    let temperament_clone = temperament.clone();
+    let tonic_clone = tonic.clone();
+    let gate_threshold_clone = gate_threshold_dbfs.clone();
+    let detection_method_clone = detection_method.clone();
+    let window_size = if args.iter().any(|arg| arg == "--window-size") {
+        cli_args.window_size
+    } else {
+        settings.window_size
+    };
+    let hop_size = cli_args.hop_size.min(window_size);
     let audio_data = Arc::new(Mutex::new(Vec::<f32>::new()));
-    let audio_data_clone = audio_data.clone();
-    let stream = device.build_input_stream(
-        &config.into(),
-        move |data: &[f32], _| {
-            let mut buffer = audio_data_clone.lock().unwrap();
-            buffer.extend_from_slice(data);
-        },
-        move |err| eprintln!("Stream error: {:?}", err),
-        None,
-    )?;
-    stream.play()?;
+    let audio_data_for_app = audio_data.clone();
+    // Signaled by the capture callback after each push; the analysis
+    // thread waits on it (with a timeout) instead of polling on a timer.
+    let audio_ready = Arc::new(Condvar::new());
+    let audio_ready_clone = audio_ready.clone();
+    let recording = Arc::new(Mutex::new(None::<Vec<f32>>));
+    let input_level = Arc::new(Mutex::new(InputLevel {
+        peak: 0.0,
+        rms: 0.0,
+        clipped: false,
+    }));
+    let input_level_for_app = input_level.clone();
+    let clipping = Arc::new(Mutex::new(false));
+    let clipping_clone = clipping.clone();
+    let no_signal = Arc::new(Mutex::new(false));
+    let no_signal_clone = no_signal.clone();
+    // A failed audio setup keeps the GUI alive to explain the problem; we
+    // fall back to a nominal sample rate so the display axes stay sane.
+    let mut startup_error = None;
+    let mut sample_rate = 44100usize;
+    let channel_selection = Arc::new(Mutex::new(settings.channel_selection));
+    let stream = match start_input_stream(
+        &audio_data,
+        &recording,
+        &input_level,
+        &channel_selection,
+        &audio_ready,
+        settings.internal_sample_rate,
+    ) {
+        Ok((stream, rate)) => {
+            sample_rate = rate;
+            Some(stream)
+        }
+        Err(message) => {
+            startup_error = Some(message);
+            None
+        }
+    };
+    // The analysis buffer is capped at MAX_BUFFER_SECONDS, so a window
+    // beyond that can never fill; surface the problem instead of letting
+    // the analysis loop wait for samples that will never arrive.
+    if startup_error.is_none() && window_size > sample_rate * MAX_BUFFER_SECONDS {
+        startup_error = Some(format!(
+            "Window size {} exceeds the {} s analysis buffer ({} samples); choose a smaller --window-size",
+            window_size,
+            MAX_BUFFER_SECONDS,
+            sample_rate * MAX_BUFFER_SECONDS
+        ));
+    }
 
+    let sample_rate = Arc::new(Mutex::new(sample_rate));
+    let sample_rate_clone = sample_rate.clone();
+    let analysis_enabled = startup_error.is_none();
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_clone = shutdown.clone();
     std::thread::spawn(move || {
+        // With a startup error on display there is nothing to analyze;
+        // exit instead of polling a buffer that can never fill.
+        if !analysis_enabled {
+            return;
+        }
+        // Keep the gate open briefly after the level drops so short dips
+        // during a sustained note don't flicker the display.
+        let gate_hold = Duration::from_millis(400);
+        let mut last_above_threshold = std::time::Instant::now();
+        // Audio time advances by one hop per consumed STFT frame.
+        let mut hops_processed = 0usize;
+        // Recent raw frequencies for the median jitter filter.
+        // Flush the median window when the pitch jumps by over a semitone
+        // so a slide to a new note snaps instead of averaging the glide.
+        let mut pitch_smoother = PitchSmoother::new(1, 100.0);
+        // Plan the FFT once instead of on every 10 ms iteration, and
+        // re-plan only when the low-latency toggle changes the geometry.
+        let mut stft_processor = StftProcessor::new(window_size, hop_size);
+        let mut planned_frame = (window_size, hop_size);
+        // Window chosen by the adaptive feedback loop; follows the pitch
+        // detected on previous iterations.
+        let mut adaptive_size = window_size;
+        // Sorted note targets for nearest-neighbor lookup, rebuilt only
+        // when the temperament or tonic changes.
+        let mut note_table_key = (
+            *lock_or_recover(&temperament_clone),
+            *lock_or_recover(&tonic_clone),
+            *lock_or_recover(&a4_reference_clone),
+        );
+        let mut note_table = NoteTable::with_a4(note_table_key.0, note_table_key.1, note_table_key.2);
+        // Last Note On sent, and the note waiting out the retrigger hold.
+        let mut last_sent_midi: Option<u8> = None;
+        let mut midi_candidate: Option<u8> = None;
+        let mut midi_candidate_since = std::time::Instant::now();
         loop {
-            sleep(Duration::from_millis(10));
-            let mut buffer = match audio_data.lock() {
-                Ok(guard) => guard,
-                Err(poisoned) => poisoned.into_inner(),
+            if shutdown_clone.load(Ordering::Relaxed) {
+                return;
+            }
+            let mut buffer = lock_or_recover(&audio_data);
+            let (window_size, hop_size) =
+                effective_frame(window_size, hop_size, *lock_or_recover(&low_latency_clone));
+            // Adaptive sizing takes precedence over the low-latency
+            // scaling: it already picks short windows for high notes.
+            let (window_size, hop_size) = if *lock_or_recover(&adaptive_window_clone) {
+                (adaptive_size, adaptive_size / 2)
+            } else {
+                (window_size, hop_size)
             };
-            if buffer.len() < window_size {
+            // The hop is baked into the processor at construction, and
+            // mode toggles can change it without touching the window
+            // (e.g. adaptive 4096/2048 vs a custom --hop-size), so watch
+            // both dimensions.
+            if planned_frame != (window_size, hop_size) {
+                stft_processor = StftProcessor::new(window_size, hop_size);
+                planned_frame = (window_size, hop_size);
+                pitch_smoother.clear();
+            }
+            // Sleep until the callback signals new audio rather than
+            // polling on a fixed period; the wait releases the buffer
+            // lock, so capture keeps flowing. The timeout caps the wait
+            // so the shutdown flag is still checked while the input is
+            // silent or the stream is stopped.
+            while buffer.len() < window_size {
+                let (reacquired, _) = audio_ready_clone
+                    .wait_timeout(buffer, Duration::from_millis(50))
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                buffer = reacquired;
+                if shutdown_clone.load(Ordering::Relaxed) {
+                    return;
+                }
+            }
+            // Re-read every iteration so a stream rebuilt on a device with
+            // a different rate takes effect without restarting the thread.
+            // Clamp to 1 so a bogus rate can never divide by zero.
+            let sample_rate = (*lock_or_recover(&sample_rate_clone)).max(1);
+            // This pass looks at the whole accumulated buffer, so every
+            // exit from the iteration drains what the STFT would consume.
+            let (frames_consumed, drain_len) =
+                consumed_by_analysis(buffer.len(), window_size, hop_size);
+
+            // Zero out NaN/Inf samples from driver glitches before they
+            // reach the metering or the FFT; a window that comes out as
+            // pure silence has nothing worth detecting.
+            let has_signal = sanitize_window(&mut buffer[..window_size]);
+            *lock_or_recover(&no_signal_clone) = !has_signal;
+            if !has_signal {
+                *lock_or_recover(&note_clone) = "—".to_string();
+                pitch_smoother.clear();
+                buffer.drain(..drain_len);
+                hops_processed += frames_consumed;
                 continue;
             }
 
-            let stft_frames = compute_short_time_fourier_transform(&buffer, window_size, hop_size);
-            if stft_frames.is_empty() {
-                let drain_len = hop_size.min(buffer.len());
+            *lock_or_recover(&clipping_clone) = is_clipping(&buffer[..window_size]);
+            let window_rms = rms(&buffer[..window_size]);
+            let level_dbfs = 20.0 * window_rms.max(f32::EPSILON).log10();
+            if level_dbfs >= *lock_or_recover(&gate_threshold_clone) {
+                last_above_threshold = std::time::Instant::now();
+            } else if last_above_threshold.elapsed() > gate_hold {
+                *lock_or_recover(&note_clone) = "—".to_string();
+                // Don't carry stale frequencies into the next note.
+                pitch_smoother.clear();
                 buffer.drain(..drain_len);
+                hops_processed += frames_consumed;
                 continue;
             }
-            let frequency_magnitudes = stft_frames
-                .iter()
-                .map(|frame| {
-                    frame[..window_size / 2]
-                        .iter()
-                        .map(|v| v.norm())
-                        .collect::<Vec<f32>>()
-                })
-                .collect::<Vec<Vec<f32>>>();
-            if frequency_magnitudes.is_empty() || frequency_magnitudes[0].is_empty() {
-                let drain_len = hop_size.min(buffer.len());
+
+            // Optional treble tilt before the STFT; the raw buffer is kept
+            // for metering and time-domain detection.
+            let emphasized = if *lock_or_recover(&pre_emphasis_enabled_clone) {
+                Some(pre_emphasis(
+                    &buffer,
+                    *lock_or_recover(&pre_emphasis_coefficient_clone),
+                ))
+            } else {
+                None
+            };
+            let analysis_input: &[f32] = emphasized.as_deref().unwrap_or(&buffer[..]);
+
+            let aggregation = *lock_or_recover(&frame_aggregation_clone);
+            let mut average_magnitudes_per_bin =
+                if aggregation == FrameAggregation::SingleFrame {
+                    // One windowed FFT over the newest window; skips the
+                    // multi-frame transform so fast pitch changes aren't
+                    // smeared by averaging older frames.
+                    single_frame_magnitudes(analysis_input, window_size)
+                } else {
+                    aggregate_magnitudes(&stft_processor.magnitudes(analysis_input), aggregation)
+                };
+            if average_magnitudes_per_bin.is_empty() {
                 buffer.drain(..drain_len);
+                hops_processed += frames_consumed;
                 continue;
             }
 
-            let num_bins = frequency_magnitudes[0].len();
-            let num_frames = frequency_magnitudes.len();
-            let mut average_magnitudes_per_bin = vec![0.0f32; num_bins];
-            for frame in &frequency_magnitudes {
-                for (bin_idx, mag) in frame.iter().enumerate() {
-                    average_magnitudes_per_bin[bin_idx] += *mag;
-                }
+            // The display gets a temporally smoothed copy so the plot
+            // doesn't flicker; detection keeps using the raw magnitudes.
+            smooth_spectrum(
+                &mut lock_or_recover(&spectrum_clone),
+                &average_magnitudes_per_bin,
+                (*lock_or_recover(&spectrum_smoothing_clone)).clamp(0.05, 1.0),
+            );
+
+            let clarity = spectral_clarity(&average_magnitudes_per_bin);
+            *lock_or_recover(&confidence_clone) = clarity;
+            if clarity < *lock_or_recover(&confidence_threshold_clone) {
+                // Too ambiguous to call a note (e.g. speech or broadband
+                // noise): keep the display blank rather than guessing.
+                *lock_or_recover(&note_clone) = "—".to_string();
+                pitch_smoother.clear();
+                buffer.drain(..drain_len);
+                hops_processed += frames_consumed;
+                continue;
+            }
+
+            // Keep the display full-range but search for peaks only in the
+            // configured band.
+            let band_min = *lock_or_recover(&band_min_clone);
+            let band_max = (*lock_or_recover(&band_max_clone)).max(band_min);
+            band_limit(
+                &mut average_magnitudes_per_bin,
+                sample_rate,
+                window_size,
+                band_min,
+                band_max,
+            );
+            let mains_notch = *lock_or_recover(&mains_notch_clone);
+            if mains_notch > 0.0 {
+                // One bin width either side, so the notch always covers the
+                // bin the hum actually lands in.
+                notch_out(
+                    &mut average_magnitudes_per_bin,
+                    sample_rate,
+                    window_size,
+                    mains_notch,
+                    sample_rate as f32 / window_size as f32,
+                );
             }
-            for mag in &mut average_magnitudes_per_bin {
-                *mag /= num_frames as f32;
+
+            if *lock_or_recover(&whitening_clone) {
+                // Wide enough to smooth across partials without flattening
+                // the peaks themselves.
+                whiten_spectrum(&mut average_magnitudes_per_bin, 32);
             }
 
-            if let Some((strongest_bin_idx, _)) = average_magnitudes_per_bin
+            let freq_resolution = sample_rate as f32 / window_size as f32;
+
+            // Interval trainer: when two clear peaks are present, report
+            // both notes, the interval between them, and the beat rate.
+            let peaks = top_two_peaks(&average_magnitudes_per_bin);
+            *lock_or_recover(&interval_clone) = if peaks.len() == 2 {
+                let mut low = peaks[0] as f32 * freq_resolution;
+                let mut high = peaks[1] as f32 * freq_resolution;
+                if low > high {
+                    std::mem::swap(&mut low, &mut high);
+                }
+                let active_temperament = *lock_or_recover(&temperament_clone);
+                let active_tonic = *lock_or_recover(&tonic_clone);
+                let readout_a4 = *lock_or_recover(&a4_reference_clone);
+                match (
+                    frequency_to_note_at(low, active_temperament, active_tonic, readout_a4),
+                    frequency_to_note_at(high, active_temperament, active_tonic, readout_a4),
+                ) {
+                    (Some((low_note, _)), Some((high_note, _))) => {
+                        let cents = cents_offset(high, low);
+                        let name = interval_name(cents).unwrap_or("beyond an octave");
+                        Some(format!(
+                            "{} + {}: {} ({:.0} cents), beat {:.1} Hz",
+                            low_note,
+                            high_note,
+                            name,
+                            cents,
+                            high - low
+                        ))
+                    }
+                    _ => None,
+                }
+            } else {
+                None
+            };
+
+            // Chord mode runs alongside the single-note pipeline so the
+            // meter keeps tracking the strongest note.
+            if *lock_or_recover(&polyphonic_clone) {
+                let active_temperament = *lock_or_recover(&temperament_clone);
+                let active_tonic = *lock_or_recover(&tonic_clone);
+                let notes: Vec<String> = detect_polyphonic_pitches(
+                    &average_magnitudes_per_bin,
+                    sample_rate,
+                    window_size,
+                    4,
+                )
                 .iter()
-                .enumerate()
-                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
-            {
-                let freq_resolution = sample_rate as f32 / window_size as f32;
-                let dominant_freq = strongest_bin_idx as f32 * freq_resolution;
+                .filter_map(|&freq| {
+                    frequency_to_note_at(
+                        freq,
+                        active_temperament,
+                        active_tonic,
+                        *lock_or_recover(&a4_reference_clone),
+                    )
+                    .map(|(note, _)| note)
+                })
+                .collect();
+                *lock_or_recover(&chord_notes_clone) = notes;
+            } else {
+                lock_or_recover(&chord_notes_clone).clear();
+            }
+            let dominant_freq = match *lock_or_recover(&detection_method_clone) {
+                DetectionMethod::SpectralPeak => {
+                    // Most prominent in-band peak rather than the global
+                    // argmax, which a broad noise hump can fool. The
+                    // prominence floor is relative to the loudest bin so
+                    // the picker follows level changes; if nothing clears
+                    // it the argmax remains as a fallback.
+                    let ceiling = average_magnitudes_per_bin
+                        .iter()
+                        .cloned()
+                        .fold(0.0f32, f32::max);
+                    find_peaks(&average_magnitudes_per_bin, ceiling * 0.05, 2)
+                        .first()
+                        .copied()
+                        .or_else(|| strongest_bin(&average_magnitudes_per_bin))
+                        .map(|bin| {
+                            refine_peak_bin(&average_magnitudes_per_bin, bin) * freq_resolution
+                        })
+                }
+                DetectionMethod::HarmonicProduct => {
+                    let harmonics = (*lock_or_recover(&hps_harmonics_clone)).clamp(2, 6);
+                    // The product spectrum locates the right bin; the
+                    // sub-bin position is read off the raw spectrum, whose
+                    // peak shape parabolic refinement models well.
+                    strongest_bin(&harmonic_product_spectrum(
+                        &average_magnitudes_per_bin,
+                        harmonics,
+                    ))
+                    .map(|bin| {
+                        refine_peak_bin(&average_magnitudes_per_bin, bin) * freq_resolution
+                    })
+                }
+                DetectionMethod::Cepstrum => {
+                    cepstrum_pitch(&average_magnitudes_per_bin, sample_rate, window_size)
+                }
+                DetectionMethod::ZeroCrossing => {
+                    zero_crossing_pitch(&buffer[..window_size], sample_rate)
+                }
+                // Time-domain periodicity; immune to the louder-harmonic
+                // traps the spectral methods fall into on low strings.
+                DetectionMethod::Yin => {
+                    YinDetector { threshold: 0.15 }.detect(&buffer[..window_size], sample_rate)
+                }
+                DetectionMethod::Mpm => MpmDetector {
+                    clarity_threshold: *lock_or_recover(&mpm_clarity_threshold_clone),
+                }
+                .detect(&buffer[..window_size], sample_rate),
+            };
+            // Systematic mic/clock error correction measured by the user
+            // against a known reference tone.
+            let dominant_freq =
+                dominant_freq.map(|freq| freq * *lock_or_recover(&calibration_clone));
+            if let Some(dominant_freq) = dominant_freq {
+
+                pitch_smoother.set_max_frames(*lock_or_recover(&smoothing_frames_clone));
+                let smoothed_freq = pitch_smoother.push(dominant_freq);
+                if *lock_or_recover(&adaptive_window_clone) {
+                    adaptive_size = adaptive_window_size(smoothed_freq, adaptive_size);
+                }
+                *lock_or_recover(&inharmonicity_clone) = estimate_inharmonicity(
+                    &average_magnitudes_per_bin,
+                    smoothed_freq,
+                    freq_resolution,
+                );
 
-                if let Some((note_name, _)) = frequency_to_note(dominant_freq) {
-                    *note_clone.lock().unwrap() = note_name.clone();
-                    *freq_clone.lock().unwrap() = dominant_freq;
+                let active_temperament = *lock_or_recover(&temperament_clone);
+                let active_tonic = *lock_or_recover(&tonic_clone);
+                let active_a4 = *lock_or_recover(&a4_reference_clone);
+                if note_table_key != (active_temperament, active_tonic, active_a4) {
+                    note_table_key = (active_temperament, active_tonic, active_a4);
+                    note_table = NoteTable::with_a4(active_temperament, active_tonic, active_a4);
+                }
+                // In target mode the offset is measured against the chosen
+                // note no matter which note is actually nearest.
+                let matched_note = match *lock_or_recover(&tuner_mode_clone) {
+                    TunerMode::Chromatic => {
+                        // An active instrument preset snaps to the nearest
+                        // open string instead of the nearest chromatic note.
+                        if let Some(preset_idx) = *lock_or_recover(&instrument_preset_clone) {
+                            nearest_preset_string(
+                                smoothed_freq,
+                                &INSTRUMENT_PRESETS[preset_idx],
+                            )
+                            .map(|(label, target)| (format!("{} string", label), target))
+                        } else {
+                            let scala = lock_or_recover(&scala_scale_clone);
+                            if let Some((scale, base)) = scala.as_ref() {
+                                // A loaded Scala scale replaces both the
+                                // chromatic table and the EDO grid; its
+                                // kbm anchor is absolute, so neither the
+                                // A4 reference nor the tonic applies.
+                                scale.lookup(smoothed_freq, *base)
+                            } else if *lock_or_recover(&edo_divisions_clone) == 12 {
+                                note_table.lookup(smoothed_freq)
+                            } else {
+                                let divisions = *lock_or_recover(&edo_divisions_clone);
+                                // Temperaments are defined on twelve notes,
+                                // so microtonal scales always use equal
+                                // steps; the A4 reference still applies.
+                                frequency_to_edo_note(smoothed_freq * 440.0 / active_a4, divisions)
+                                    .map(|(note, target)| (note, target * active_a4 / 440.0))
+                            }
+                        }
+                    }
+                    TunerMode::Target => {
+                        let index = *lock_or_recover(&target_note_index_clone);
+                        let octave = *lock_or_recover(&target_octave_clone);
+                        let target_freq =
+                            apply_a4_reference(
+                                note_frequencies(active_temperament, active_tonic),
+                                active_a4,
+                            )[index] * 2f32.powi(octave - 4);
+                        Some((format!("{}{}", NOTES[index].0, octave), target_freq))
+                    }
+                    TunerMode::Ensemble => {
+                        // Snap to whichever of the user's targets is
+                        // nearest in cents, so each player's note reads
+                        // against their own line of the panel.
+                        let frequencies = apply_a4_reference(
+                            note_frequencies(active_temperament, active_tonic),
+                            active_a4,
+                        );
+                        lock_or_recover(&ensemble_targets_clone)
+                            .iter()
+                            .map(|&(index, octave)| {
+                                (
+                                    format!("{}{}", NOTES[index].0, octave),
+                                    frequencies[index] * 2f32.powi(octave - 4),
+                                )
+                            })
+                            .min_by(|a, b| {
+                                cents_offset(smoothed_freq, a.1)
+                                    .abs()
+                                    .total_cmp(&cents_offset(smoothed_freq, b.1).abs())
+                            })
+                    }
+                };
+                if let Some((note_name, note_freq)) = matched_note {
+                    let cents = cents_offset(smoothed_freq, note_freq);
+                    debug!(
+                        "Detected {:.2} Hz as {} ({:+.1} cents)",
+                        smoothed_freq, note_name, cents
+                    );
+                    *lock_or_recover(&note_clone) = note_name.clone();
+                    *lock_or_recover(&freq_clone) = smoothed_freq;
+                    *lock_or_recover(&cents_clone) = cents;
+                    lock_or_recover(&drift_stats_clone).push(cents);
+                    lock_or_recover(&pitch_track_clone).push(PitchRecord {
+                        timestamp_seconds: hops_processed as f32 * hop_size as f32
+                            / sample_rate as f32,
+                        frequency: smoothed_freq,
+                        note: note_name,
+                        cents_offset: cents,
+                    });
+
+                    // Pitch-to-MIDI: retrigger only after the rounded note
+                    // has held steady for the configured minimum duration.
+                    let midi_note =
+                        frequency_to_midi(smoothed_freq).map(|m| m.round().clamp(0.0, 127.0) as u8);
+                    if let Some(midi) = midi_note {
+                        if Some(midi) == last_sent_midi {
+                            midi_candidate = Some(midi);
+                        } else if midi_candidate != Some(midi) {
+                            midi_candidate = Some(midi);
+                            midi_candidate_since = std::time::Instant::now();
+                        } else {
+                            let min_hold =
+                                Duration::from_millis(*lock_or_recover(&midi_min_hold_clone));
+                            if midi_candidate_since.elapsed() >= min_hold {
+                                if let Some(previous) = last_sent_midi {
+                                    send_midi_message(
+                                        &midi_connection_clone,
+                                        &[0x80, previous, 0],
+                                    );
+                                }
+                                send_midi_message(&midi_connection_clone, &[0x90, midi, 100]);
+                                last_sent_midi = Some(midi);
+                            }
+                        }
+                    }
                 }
             }
 
-            let drain_len = hop_size.min(buffer.len());
             buffer.drain(..drain_len);
+            hops_processed += frames_consumed;
         }
     });
 
     let app = Rustique {
         detected_note,
         detected_freq,
+        temperament,
+        note_spelling,
+        tonic,
+        transposition: 0,
+        gate_threshold_dbfs,
+        detection_method,
+        frame_aggregation,
+        band_min_hz,
+        band_max_hz,
+        mains_notch_hz,
+        tuner_mode,
+        instrument_preset,
+        target_note_index,
+        ensemble_targets,
+        target_octave,
+        smoothing_frames,
+        low_latency,
+        adaptive_window,
+        whitening,
+        pre_emphasis: pre_emphasis_enabled,
+        pre_emphasis_coefficient,
+        mpm_clarity_threshold,
+        hps_harmonics,
+        spectrum_smoothing,
+        edo_divisions,
+        detected_cents,
+        polyphonic,
+        calibration,
+        a4_reference,
+        scala_scale,
+        chord_notes,
+        interval_display,
+        confidence,
+        confidence_threshold,
+        inharmonicity,
+        detected_midi: None,
+        midi_connection,
+        midi_min_hold_ms,
+        midi_port_selection: 0,
+        midi_status: None,
+        reference_tone: Arc::new(Mutex::new(None)),
+        tone_stream: None,
+        tone_status: None,
+        tone_octave_shift: 0,
+        input_stream: stream,
+        shutdown,
+        metronome: Arc::new(Mutex::new(MetronomeSettings {
+            running: false,
+            bpm: 120,
+            beats_per_bar: 4,
+        })),
+        metronome_stream: None,
+        metronome_status: None,
+        latest_spectrum,
+        pitch_track,
+        drift_stats,
+        audio_data: audio_data_for_app,
+        input_level: input_level_for_app,
+        clipping,
+        no_signal,
+        channel_selection,
+        recording,
+        sample_rate,
+        window_size,
+        hop_size,
+        save_status: None,
+        scala_path: String::new(),
+        scala_status: None,
+        internal_sample_rate: settings.internal_sample_rate,
+        dark_theme: settings.dark_theme,
+        font_scale: settings.font_scale,
+        color_scheme: settings.color_scheme,
+        strobe_mode: settings.strobe_mode,
+        hold_enabled: false,
+        held_reading: None,
+        piano_mode: false,
+        self_test_status: None,
+        spectrum_db: false,
+        freeze_enabled: false,
+        frozen_spectrum: Vec::new(),
+        frozen_waveform: Vec::new(),
+        spectrum_a_weight: false,
+        startup_error,
+        needle_cents: 0.0,
+        pitch_indicator: PitchIndicator::InTune,
+        cents_history: Vec::new(),
+        strobe_phase: 0.0,
+        last_saved_settings: Settings {
+            window_size,
+            ..settings
+        },
     };
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
@@ -147,221 +3281,164 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn frequency_to_note(freq: f32) -> Option<(String, f32)> {
-    if freq <= 0.0 {
-        return None;
-    }
-    let mut closest_note = None;
-    let mut min_diff = f32::MAX;
-    let mut closest_octave = 0;
-    for octave in 0..8 {
-        for (name, base_freq) in NOTES.iter() {
-            let note_freq = base_freq * 2f32.powi(octave - 4);
-            let diff = (freq - note_freq).abs();
-            if diff < min_diff {
-                min_diff = diff;
-                closest_note = Some((name, note_freq));
-                closest_octave = octave;
-            }
-        }
-    }
-    closest_note.map(|(name, note_freq)| (format!("{}{}", name, closest_octave), note_freq))
-}
-
-fn _plot_average_magnitudes_with_bins(
-    average_magnitudes: &[f32],
-    bin_centers: &[f32],
-) -> Result<(), Box<dyn std::error::Error>> {
-    let root = BitMapBackend::new("average_magnitudes_bins.png", (900, 600)).into_drawing_area();
-    root.fill(&WHITE)?;
-
-    let max_magnitude = average_magnitudes.iter().cloned().fold(f32::MIN, f32::max);
-
-    // Define x range from min to max bin center
-    let x_min = *bin_centers.first().unwrap_or(&0.0);
-    let x_max = *bin_centers.last().unwrap_or(&0.0);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut chart = ChartBuilder::on(&root)
-        .caption(
-            "Average Magnitudes per Frequency Bin",
-            ("sans-serif", 30).into_font(),
-        )
-        .margin(20)
-        .x_label_area_size(60)
-        .y_label_area_size(60)
-        .build_cartesian_2d(x_min..x_max, 0f32..max_magnitude)?;
-
-    // Customize x axis to show bin centers with formatted labels
-    chart
-        .configure_mesh()
-        .x_desc("Frequency (Hz)")
-        .y_desc("Average Magnitude")
-        .x_labels(20)
-        .x_label_formatter(&|x| format!("{:.1}", x))
-        .draw()?;
-
-    // Connect points with line
-    chart.draw_series(LineSeries::new(
-        bin_centers
-            .iter()
-            .zip(average_magnitudes.iter())
-            .map(|(center, mag)| (*center, *mag)),
-        &BLUE,
-    ))?;
-
-    Ok(())
-}
-
-fn _compute_bin_ranges(sample_rate: usize, window_size: usize) -> Vec<(f32, f32)> {
-    let bin_width = sample_rate as f32 / window_size as f32;
-    let half_n = window_size / 2;
-    (0..half_n)
-        .map(|i| {
-            let center = i as f32 * bin_width;
-            (center - bin_width / 2.0, center + bin_width / 2.0)
-        })
-        .collect()
-}
-
-fn compute_short_time_fourier_transform(
-    buffer: &[f32],
-    window_size: usize,
-    hop_size: usize,
-) -> Vec<Vec<Complex32>> {
-    let mut planner = FftPlanner::<f32>::new();
-    let fft = planner.plan_fft_forward(window_size);
-    let hann: Vec<f32> = (0..window_size)
-        .map(|i| (PI * 2.0 * i as f32 / window_size as f32).sin().powi(2))
-        .collect();
-    let mut spectrum = Vec::new();
-    let mut pos = 0;
-
-    while pos + window_size <= buffer.len() {
-        let mut windowed: Vec<Complex32> = buffer[pos..pos + window_size]
-            .iter()
-            .zip(hann.iter())
-            .map(|(sample, w)| Complex32::new(sample * w, 0.0))
-            .collect();
-
-        fft.process(&mut windowed);
-        spectrum.push(windowed);
-        pos += hop_size;
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
     }
 
-    spectrum
-}
-
-fn _read_wav(path: &str) -> Result<(usize, Vec<f32>), Box<dyn Error>> {
-    let reader = WavReader::open(path)?;
-    let spec = reader.spec();
-
-    let samples: Vec<f32> = match spec.sample_format {
-        SampleFormat::Float => reader
-            .into_samples::<f32>()
-            .map(|s| s.map_err(|e| e.into()))
-            .collect::<Result<_, Box<dyn Error>>>()?,
-        SampleFormat::Int => {
-            let max_amplitude = 2_i32.pow(spec.bits_per_sample as u32 - 1) as f32;
-            reader
-                .into_samples::<i32>()
-                .map(|s| Ok(s? as f32 / max_amplitude))
-                .collect::<Result<_, Box<dyn Error>>>()?
-        }
-    };
+    #[test]
+    fn cli_defaults_to_half_window_hop() {
+        let parsed = parse_cli_args(&args(&["--window-size", "8192"])).unwrap();
+        assert_eq!(parsed.window_size, 8192);
+        assert_eq!(parsed.hop_size, 4096);
+    }
 
-    Ok((spec.sample_rate as usize, samples))
-}
+    #[test]
+    fn cli_rejects_non_power_of_two_window() {
+        assert!(parse_cli_args(&args(&["--window-size", "5000"])).is_err());
+    }
 
-fn _write_wav(
-    path: &str,
-    samples: &[f32],
-    sample_rate: usize,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate: sample_rate as u32,
-        bits_per_sample: 32,
-        sample_format: SampleFormat::Float,
-    };
+    #[test]
+    fn cli_rejects_hop_larger_than_window() {
+        assert!(parse_cli_args(&args(&["--window-size", "1024", "--hop-size", "2048"])).is_err());
+    }
 
-    let mut writer = WavWriter::create(path, spec)?;
-    for &sample in samples {
-        writer.write_sample(sample.clamp(-1.0, 1.0))?;
+    #[test]
+    fn cli_parses_analyze_with_spectrogram() {
+        let parsed =
+            parse_cli_args(&args(&["analyze", "take.wav", "--spectrogram", "out.png"])).unwrap();
+        let analyze = parsed.analyze.unwrap();
+        assert_eq!(analyze.input, "take.wav");
+        assert_eq!(analyze.spectrogram.as_deref(), Some("out.png"));
     }
-    writer.finalize()?;
-    Ok(())
-}
 
-fn _plot_spectrum(freqs: &[f32], magnitudes: &[f32], filename: &str) -> Result<(), Box<dyn Error>> {
-    let root = BitMapBackend::new(filename, (1024, 768)).into_drawing_area();
-    root.fill(&WHITE)?;
+    #[test]
+    fn cli_rejects_spectrogram_without_analyze() {
+        assert!(parse_cli_args(&args(&["--spectrogram", "out.png"])).is_err());
+    }
 
-    let max_y = magnitudes.iter().copied().fold(0.0_f32, f32::max);
-    let x_max = freqs.last().copied().unwrap_or(1.0);
+    #[test]
+    fn cli_rejects_a_malformed_serve_port() {
+        assert!(parse_cli_args(&args(&["--serve", "not-a-port"])).is_err());
+        assert!(parse_cli_args(&args(&["--serve"])).is_err());
+    }
 
-    let mut chart = ChartBuilder::on(&root)
-        .caption("Frequency Specturm", ("sans-serif", 40))
-        .margin(10)
-        .x_label_area_size(40)
-        .y_label_area_size(60)
-        .build_cartesian_2d(0.0_f32..x_max, 0.0_f32..max_y)?;
+    #[test]
+    fn cli_rejects_format_without_analyze() {
+        assert!(parse_cli_args(&args(&["--format", "json"])).is_err());
+        assert!(parse_cli_args(&args(&["analyze", "take.wav", "--format", "xml"])).is_err());
+        let parsed = parse_cli_args(&args(&["analyze", "take.wav", "--format", "csv"])).unwrap();
+        assert_eq!(parsed.analyze.unwrap().format, OutputFormat::Csv);
+    }
 
-    chart
-        .configure_mesh()
-        .x_desc("Frequency (Hz)")
-        .y_desc("Magnitude")
-        .draw()?;
+    #[test]
+    fn drain_length_matches_the_frames_actually_consumed() {
+        // Three windows' worth of audio yields five overlapping frames;
+        // draining five hops leaves exactly the window-hop overlap.
+        assert_eq!(consumed_by_analysis(4096 * 3, 4096, 2048), (5, 10240));
+        assert_eq!(4096 * 3 - 10240, 4096 - 2048);
+        // Exactly one window: one frame, one hop drained.
+        assert_eq!(consumed_by_analysis(4096, 4096, 2048), (1, 2048));
+        // No overlap when the hop equals the window.
+        assert_eq!(consumed_by_analysis(8192, 4096, 4096), (2, 8192));
+        // Not enough audio for a single frame: nothing is consumed.
+        assert_eq!(consumed_by_analysis(4095, 4096, 2048), (0, 0));
+    }
 
-    chart.draw_series(LineSeries::new(
-        freqs.iter().zip(magnitudes.iter()).map(|(&x, &y)| (x, y)),
-        &BLUE,
-    ))?;
+    #[test]
+    fn time_region_slices_and_validates_the_selection() {
+        let sample_rate = 44100;
+        let samples_len = sample_rate * 3;
+        // A one-second selection maps to exactly one second of samples.
+        let region = time_region(samples_len, sample_rate, Some(1.0), Some(2.0)).unwrap();
+        assert_eq!(region.len(), sample_rate);
+        assert_eq!(region.start, sample_rate);
+        // Omitted bounds default to the whole file.
+        assert_eq!(
+            time_region(samples_len, sample_rate, None, None).unwrap(),
+            0..samples_len
+        );
+        // Inverted or out-of-range selections are rejected.
+        assert!(time_region(samples_len, sample_rate, Some(2.0), Some(1.0)).is_err());
+        assert!(time_region(samples_len, sample_rate, Some(0.0), Some(4.0)).is_err());
+        assert!(time_region(samples_len, sample_rate, Some(-0.5), None).is_err());
+    }
 
-    root.present()?;
-    Ok(())
-}
+    #[test]
+    fn cli_rejects_batch_outside_plain_analyze() {
+        assert!(parse_cli_args(&args(&["--batch"])).is_err());
+        assert!(parse_cli_args(&args(&["analyze", "takes/", "--batch", "--format", "json"])).is_err());
+        assert!(
+            parse_cli_args(&args(&["analyze", "takes/", "--batch", "--spectrogram", "s.png"]))
+                .is_err()
+        );
+        let parsed = parse_cli_args(&args(&["analyze", "takes/", "--batch"])).unwrap();
+        assert!(parsed.analyze.unwrap().batch);
+    }
 
-fn _plot_waveform(
-    samples: &[f32],
-    sample_rate: usize,
-    filename: &str,
-) -> Result<(), Box<dyn Error>> {
-    let root = BitMapBackend::new(filename, (1024, 512)).into_drawing_area();
-    root.fill(&WHITE)?;
+    #[test]
+    fn reference_tone_doubles_per_octave_step() {
+        let base = reference_tone_frequency(440.0, 4, 0, 44100).unwrap();
+        assert_eq!(base, 440.0);
+        assert_eq!(reference_tone_frequency(440.0, 4, 1, 44100).unwrap(), base * 2.0);
+        assert_eq!(reference_tone_frequency(440.0, 4, -1, 44100).unwrap(), base / 2.0);
+        assert_eq!(reference_tone_frequency(440.0, 3, 1, 44100).unwrap(), base);
+        // 13.75 Hz and 28.16 kHz are refused, not synthesized.
+        assert!(reference_tone_frequency(440.0, 4, -5, 44100).is_err());
+        assert!(reference_tone_frequency(440.0, 4, 6, 44100).is_err());
+    }
 
-    // Determine time range in seconds
-    let duration = samples.len() as f32 / sample_rate as f32;
+    #[test]
+    fn json_frame_lines_are_parseable() {
+        let frame = PitchFrame {
+            time: 1.5,
+            frequency: 440.0,
+            note: Some("A4".to_string()),
+            cents: -2.3,
+            confidence: 0.9,
+        };
+        let value: serde_json::Value = serde_json::from_str(&format_frame_json(&frame)).unwrap();
+        assert_eq!(value["time"], 1.5);
+        assert_eq!(value["freq"], 440.0);
+        assert_eq!(value["note"], "A4");
+        assert_eq!(value["cents"], -2.3);
+        // A frame outside the note range serializes the note as null.
+        let unpitched = PitchFrame { note: None, ..frame };
+        let value: serde_json::Value =
+            serde_json::from_str(&format_frame_json(&unpitched)).unwrap();
+        assert!(value["note"].is_null());
+        assert_eq!(format_frame_csv(&unpitched), "1.500,440.00,,-2.3,0.900");
+    }
 
-    // Find the min/max amplitude for Y-axis scaling
-    let (min_y, max_y) = samples
-        .iter()
-        .fold((f32::MAX, f32::MIN), |(min, max), &val| {
-            (min.min(val), max.max(val))
-        });
+    #[test]
+    fn cli_accepts_headless_with_analyze() {
+        let parsed = parse_cli_args(&args(&["--headless", "analyze", "take.wav"])).unwrap();
+        assert!(parsed.headless);
+        assert!(parsed.analyze.is_some());
+    }
 
-    let mut chart = ChartBuilder::on(&root)
-        .caption("Audio Waveform", ("sans-serif", 30))
-        .margin(10)
-        .x_label_area_size(40)
-        .y_label_area_size(50)
-        .build_cartesian_2d(0f32..duration, min_y..max_y)?;
-
-    chart
-        .configure_mesh()
-        .x_desc("Time (s)")
-        .y_desc("Amplitude")
-        .x_labels(10)
-        .y_labels(5)
-        .draw()?;
-
-    chart.draw_series(LineSeries::new(
-        samples
-            .iter()
-            .enumerate()
-            .map(|(i, &y)| (i as f32 / sample_rate as f32, y)),
-        &BLUE,
-    ))?;
+    #[test]
+    fn cli_rejects_headless_without_analyze() {
+        assert!(parse_cli_args(&args(&["--headless"])).is_err());
+    }
 
-    root.present()?;
-    Ok(())
+    #[test]
+    fn pitch_indicator_latches_until_clearly_back_in_tune() {
+        let mut indicator = PitchIndicator::InTune;
+        indicator = indicator.advance(-10.0);
+        assert!(indicator == PitchIndicator::Flat);
+        // Hovering just inside the entry threshold keeps the latch.
+        indicator = indicator.advance(-5.0);
+        assert!(indicator == PitchIndicator::Flat);
+        indicator = indicator.advance(-1.0);
+        assert!(indicator == PitchIndicator::InTune);
+        // Small excursions near zero don't re-trigger either arrow.
+        indicator = indicator.advance(6.0);
+        assert!(indicator == PitchIndicator::InTune);
+        indicator = indicator.advance(9.0);
+        assert!(indicator == PitchIndicator::Sharp);
+    }
 }
@@ -0,0 +1,233 @@
+//! Time-domain pitch detectors.
+//!
+//! The FFT path in the crate root picks the strongest averaged magnitude
+//! bin, which locks onto a harmonic instead of the fundamental whenever a
+//! higher partial carries more energy — a guitar's low E string being the
+//! classic case. The detectors here measure the waveform's periodicity
+//! directly, so the fundamental wins even when it is spectrally weak.
+
+/// Pitch search limits in Hz: low enough for a bass guitar's low B, high
+/// enough for the top of a violin's range.
+const MIN_FREQUENCY: f32 = 40.0;
+const MAX_FREQUENCY: f32 = 2000.0;
+
+/// YIN pitch estimate (de Cheveigné & Kawahara 2002): the first lag at
+/// which the cumulative mean normalized difference function dips below
+/// `threshold`, followed downhill to its local minimum. The normalization
+/// is what suppresses the lag-zero trough and the harmonic sub-multiples
+/// that defeat plain autocorrelation. A threshold of 0.1–0.15 is usual;
+/// returns None when no lag qualifies, i.e. the window is not periodic
+/// enough to call a pitch.
+pub fn yin_pitch(samples: &[f32], sample_rate: usize, threshold: f32) -> Option<f32> {
+    if sample_rate == 0 {
+        return None;
+    }
+    // The difference function compares a half-window against itself at
+    // every candidate lag, so the longest measurable period is half the
+    // input.
+    let window = samples.len() / 2;
+    let min_lag = (sample_rate as f32 / MAX_FREQUENCY) as usize;
+    let max_lag = ((sample_rate as f32 / MIN_FREQUENCY) as usize).min(window.saturating_sub(1));
+    if max_lag <= min_lag.max(1) {
+        return None;
+    }
+    let mut difference = vec![0.0f32; max_lag + 1];
+    for (lag, value) in difference.iter_mut().enumerate().skip(1) {
+        let mut sum = 0.0;
+        for i in 0..window {
+            let delta = samples[i] - samples[i + lag];
+            sum += delta * delta;
+        }
+        *value = sum;
+    }
+    // Cumulative mean normalization: d'(lag) = d(lag)·lag / Σ d(1..=lag),
+    // which is ~1 for aperiodic input and dips toward 0 at the period.
+    let mut normalized = vec![1.0f32; max_lag + 1];
+    let mut running_sum = 0.0;
+    for lag in 1..=max_lag {
+        running_sum += difference[lag];
+        if running_sum > 0.0 {
+            normalized[lag] = difference[lag] * lag as f32 / running_sum;
+        }
+    }
+    let mut lag = min_lag.max(2);
+    while lag <= max_lag {
+        if normalized[lag] < threshold {
+            while lag < max_lag && normalized[lag + 1] < normalized[lag] {
+                lag += 1;
+            }
+            return Some(sample_rate as f32 / lag as f32);
+        }
+        lag += 1;
+    }
+    None
+}
+
+/// McLeod Pitch Method estimate: the normalized square difference
+/// function (NSDF) `n(τ) = 2·Σ x[i]x[i+τ] / Σ (x[i]² + x[i+τ]²)` peaks
+/// near 1 at the period of a strongly periodic signal. Key maxima are
+/// collected between the NSDF's zero crossings, and the first one within
+/// 90% of the tallest is taken — the tallest alone would often be a
+/// sub-octave. The chosen maximum's height is the clarity; below
+/// `clarity_threshold` the frame counts as silence or noise and no pitch
+/// is reported, rather than a random lag.
+pub fn mpm_pitch(samples: &[f32], sample_rate: usize, clarity_threshold: f32) -> Option<f32> {
+    if sample_rate == 0 {
+        return None;
+    }
+    let window = samples.len() / 2;
+    let min_lag = (sample_rate as f32 / MAX_FREQUENCY) as usize;
+    let max_lag = ((sample_rate as f32 / MIN_FREQUENCY) as usize).min(window.saturating_sub(1));
+    if max_lag <= min_lag.max(1) {
+        return None;
+    }
+    let mut nsdf = vec![0.0f32; max_lag + 1];
+    for (lag, value) in nsdf.iter_mut().enumerate().skip(1) {
+        let mut autocorrelation = 0.0;
+        let mut energy = 0.0;
+        for i in 0..window {
+            autocorrelation += samples[i] * samples[i + lag];
+            energy += samples[i] * samples[i] + samples[i + lag] * samples[i + lag];
+        }
+        if energy > 0.0 {
+            *value = 2.0 * autocorrelation / energy;
+        }
+    }
+    // Key maxima: the peak of every positive NSDF region after the curve
+    // first leaves its initial lobe around lag zero.
+    let mut maxima: Vec<(usize, f32)> = Vec::new();
+    let mut lag = 1;
+    while lag <= max_lag && nsdf[lag] > 0.0 {
+        lag += 1;
+    }
+    while lag <= max_lag {
+        while lag <= max_lag && nsdf[lag] <= 0.0 {
+            lag += 1;
+        }
+        let mut peak: Option<(usize, f32)> = None;
+        while lag <= max_lag && nsdf[lag] > 0.0 {
+            if lag >= min_lag.max(2) && peak.is_none_or(|(_, height)| nsdf[lag] > height) {
+                peak = Some((lag, nsdf[lag]));
+            }
+            lag += 1;
+        }
+        if let Some(peak) = peak {
+            maxima.push(peak);
+        }
+    }
+    let tallest = maxima
+        .iter()
+        .map(|&(_, height)| height)
+        .fold(f32::MIN, f32::max);
+    let (best_lag, clarity) = *maxima
+        .iter()
+        .find(|&&(_, height)| height >= 0.9 * tallest)?;
+    if clarity < clarity_threshold {
+        return None;
+    }
+    Some(sample_rate as f32 / best_lag as f32)
+}
+
+/// A detector the analysis thread can swap at runtime: one window of
+/// samples in, an optional fundamental out. Thresholds and other tuning
+/// live on the implementing type.
+pub trait PitchDetector {
+    fn detect(&self, samples: &[f32], sample_rate: usize) -> Option<f32>;
+}
+
+/// [`yin_pitch`] with its difference threshold as state.
+pub struct YinDetector {
+    pub threshold: f32,
+}
+
+impl PitchDetector for YinDetector {
+    fn detect(&self, samples: &[f32], sample_rate: usize) -> Option<f32> {
+        yin_pitch(samples, sample_rate, self.threshold)
+    }
+}
+
+/// [`mpm_pitch`] with its clarity threshold as state.
+pub struct MpmDetector {
+    pub clarity_threshold: f32,
+}
+
+impl PitchDetector for MpmDetector {
+    fn detect(&self, samples: &[f32], sample_rate: usize) -> Option<f32> {
+        mpm_pitch(samples, sample_rate, self.clarity_threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn yin_finds_the_fundamental_under_a_louder_harmonic() {
+        // Low guitar E with its 2nd partial twice as strong: a spectral
+        // argmax reports the octave, YIN the string's actual pitch.
+        let sample_rate = 44100;
+        let fundamental = 82.41;
+        let samples: Vec<f32> = (0..4096 * 2)
+            .map(|i| {
+                let phase = 2.0 * PI * fundamental * i as f32 / sample_rate as f32;
+                0.3 * phase.sin() + 0.6 * (2.0 * phase).sin()
+            })
+            .collect();
+        let freq = yin_pitch(&samples, sample_rate, 0.15).unwrap();
+        assert!(
+            (freq - fundamental).abs() < 1.0,
+            "detected {} Hz for a {} Hz tone",
+            freq,
+            fundamental
+        );
+    }
+
+    #[test]
+    fn mpm_finds_the_fundamental_and_reports_clarity_failures() {
+        let sample_rate = 44100;
+        let fundamental = 110.0;
+        let samples: Vec<f32> = (0..4096 * 2)
+            .map(|i| {
+                let phase = 2.0 * PI * fundamental * i as f32 / sample_rate as f32;
+                0.4 * phase.sin() + 0.5 * (2.0 * phase).sin() + 0.2 * (3.0 * phase).sin()
+            })
+            .collect();
+        let detector = MpmDetector {
+            clarity_threshold: 0.8,
+        };
+        let freq = detector.detect(&samples, sample_rate).unwrap();
+        assert!(
+            (freq - fundamental).abs() < 1.0,
+            "detected {} Hz for a {} Hz tone",
+            freq,
+            fundamental
+        );
+        // Noise never reaches the clarity threshold.
+        let mut state = 7u32;
+        let noise: Vec<f32> = (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 16) as f32 / 32768.0 - 1.0
+            })
+            .collect();
+        assert!(detector.detect(&noise, sample_rate).is_none());
+        assert!(detector.detect(&[0.0; 4096], sample_rate).is_none());
+    }
+
+    #[test]
+    fn yin_declines_to_guess_on_noise() {
+        // Deterministic white noise: the normalized difference never dips
+        // below the threshold, so no pitch is reported.
+        let mut state = 1u32;
+        let noise: Vec<f32> = (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 16) as f32 / 32768.0 - 1.0
+            })
+            .collect();
+        assert!(yin_pitch(&noise, 44100, 0.15).is_none());
+        // Too short a buffer cannot hold a full low-frequency period.
+        assert!(yin_pitch(&[0.0; 64], 44100, 0.15).is_none());
+    }
+}
@@ -0,0 +1,308 @@
+//! Scala tuning file support.
+//!
+//! The Scala archive's `.scl` format is the lingua franca for microtonal
+//! scales: a description line, a degree count, then one pitch per line,
+//! written either as cents (any number containing a period) or as a ratio
+//! like `3/2`. The companion `.kbm` keyboard mapping anchors the scale to
+//! absolute pitch by naming a reference key and its frequency. Together
+//! they let the tuner target 22-EDO, maqam subsets, or anything else the
+//! twelve-tone temperament table cannot express.
+
+/// A parsed `.scl` scale: the degrees as cumulative cents above the 1/1,
+/// with the final entry being the period (usually a 1200-cent octave).
+pub struct ScalaScale {
+    pub description: String,
+    /// Cents above 1/1 for degrees 1..=N; `steps_cents.last()` is the
+    /// period at which the scale repeats. The implicit 1/1 is not stored.
+    pub steps_cents: Vec<f32>,
+}
+
+/// Parse `.scl` text. Lines starting with `!` are comments; the first two
+/// real lines are the description and the degree count, and each pitch
+/// line is cents (contains a `.`) or a ratio (`3/2`, bare integers mean
+/// `n/1`). Anything after the value on a pitch line is ignored, per the
+/// format specification.
+pub fn parse_scl(text: &str) -> Result<ScalaScale, String> {
+    let mut lines = text.lines().filter(|line| !line.trim_start().starts_with('!'));
+    let description = lines
+        .next()
+        .ok_or("Empty .scl file")?
+        .trim()
+        .to_string();
+    let count: usize = lines
+        .next()
+        .ok_or("Missing degree count")?
+        .trim()
+        .parse()
+        .map_err(|_| "Degree count is not a number".to_string())?;
+    let mut steps_cents = Vec::with_capacity(count);
+    for line in lines.take(count) {
+        let value = line
+            .split_whitespace()
+            .next()
+            .ok_or("Blank pitch line".to_string())?;
+        let cents = if value.contains('.') {
+            value
+                .parse::<f32>()
+                .map_err(|_| format!("Bad cents value: {}", value))?
+        } else {
+            let ratio = match value.split_once('/') {
+                Some((numerator, denominator)) => {
+                    let numerator: f32 = numerator
+                        .parse()
+                        .map_err(|_| format!("Bad ratio: {}", value))?;
+                    let denominator: f32 = denominator
+                        .parse()
+                        .map_err(|_| format!("Bad ratio: {}", value))?;
+                    if denominator <= 0.0 {
+                        return Err(format!("Bad ratio: {}", value));
+                    }
+                    numerator / denominator
+                }
+                None => value
+                    .parse::<f32>()
+                    .map_err(|_| format!("Bad ratio: {}", value))?,
+            };
+            if ratio <= 0.0 {
+                return Err(format!("Ratio must be positive: {}", value));
+            }
+            1200.0 * ratio.log2()
+        };
+        steps_cents.push(cents);
+    }
+    if steps_cents.len() != count {
+        return Err(format!(
+            "Expected {} degrees, found {}",
+            count,
+            steps_cents.len()
+        ));
+    }
+    if steps_cents.last().copied().unwrap_or(0.0) <= 0.0 {
+        return Err("The period (last degree) must be positive".to_string());
+    }
+    Ok(ScalaScale {
+        description,
+        steps_cents,
+    })
+}
+
+impl ScalaScale {
+    /// Map a frequency to the nearest scale degree given the frequency of
+    /// the 1/1, returning a label like `3\22 p4` (degree 3 of 22, period
+    /// 4, numbered like octaves with the base at period 4) and the
+    /// degree's target frequency.
+    pub fn lookup(&self, freq: f32, base_frequency: f32) -> Option<(String, f32)> {
+        if freq <= 0.0 || base_frequency <= 0.0 {
+            return None;
+        }
+        let period_cents = *self.steps_cents.last()?;
+        let cents_above_base = 1200.0 * (freq / base_frequency).log2();
+        let period = (cents_above_base / period_cents).floor();
+        let within = cents_above_base - period * period_cents;
+        // Candidate degrees in this period, plus degree 0 of the next one
+        // (the period boundary seen from below).
+        let mut best: Option<(usize, f32, f32)> = None;
+        for degree in 0..=self.steps_cents.len() {
+            let degree_cents = if degree == 0 {
+                0.0
+            } else {
+                self.steps_cents[degree - 1]
+            };
+            let distance = (within - degree_cents).abs();
+            if best.is_none_or(|(_, _, current)| distance < current) {
+                best = Some((degree, degree_cents, distance));
+            }
+        }
+        let (mut degree, degree_cents, _) = best?;
+        let mut period = period;
+        if degree == self.steps_cents.len() {
+            degree = 0;
+            period += 1.0;
+        }
+        let target_cents = period * period_cents + degree_cents;
+        let target = base_frequency * 2f32.powf(target_cents / 1200.0);
+        let label = format!(
+            "{}\\{} p{}",
+            degree,
+            self.steps_cents.len(),
+            period as i32 + 4
+        );
+        Some((label, target))
+    }
+}
+
+/// A parsed `.kbm` keyboard mapping: which key carries the 1/1 and how
+/// the scale is anchored in absolute frequency. Only the fields the tuner
+/// needs are kept; per-key mapping entries of `x` stay unmapped.
+pub struct KeyboardMapping {
+    pub middle_note: i32,
+    pub reference_note: i32,
+    pub reference_frequency: f32,
+    pub mapping: Vec<Option<usize>>,
+}
+
+/// Parse `.kbm` text: seven header numbers (map size, first and last
+/// key, middle key, reference key, reference frequency, formal octave
+/// degree) followed by one mapping entry per line.
+pub fn parse_kbm(text: &str) -> Result<KeyboardMapping, String> {
+    let mut lines = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('!'))
+        .map(str::trim);
+    let mut header = |name: &str| -> Result<f32, String> {
+        lines
+            .next()
+            .ok_or(format!("Missing {}", name))?
+            .split_whitespace()
+            .next()
+            .ok_or(format!("Missing {}", name))?
+            .parse::<f32>()
+            .map_err(|_| format!("Bad {}", name))
+    };
+    let map_size = header("map size")? as usize;
+    let _first_note = header("first note")?;
+    let _last_note = header("last note")?;
+    let middle_note = header("middle note")? as i32;
+    let reference_note = header("reference note")? as i32;
+    let reference_frequency = header("reference frequency")?;
+    let _octave_degree = header("octave degree")?;
+    if reference_frequency <= 0.0 {
+        return Err("Reference frequency must be positive".to_string());
+    }
+    let mapping: Vec<Option<usize>> = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('!'))
+        .map(str::trim)
+        .skip(7)
+        .take(map_size)
+        .map(|line| {
+            line.split_whitespace()
+                .next()
+                .and_then(|value| value.parse::<usize>().ok())
+        })
+        .collect();
+    Ok(KeyboardMapping {
+        middle_note,
+        reference_note,
+        reference_frequency,
+        mapping,
+    })
+}
+
+impl KeyboardMapping {
+    /// Frequency of the scale's 1/1 implied by the reference key and
+    /// frequency, or None when the reference key is unmapped.
+    pub fn base_frequency(&self, scale: &ScalaScale) -> Option<f32> {
+        let period_cents = *scale.steps_cents.last()?;
+        let size = if self.mapping.is_empty() {
+            scale.steps_cents.len()
+        } else {
+            self.mapping.len()
+        };
+        let offset = self.reference_note - self.middle_note;
+        let periods = offset.div_euclid(size as i32);
+        let index = offset.rem_euclid(size as i32) as usize;
+        let degree = if self.mapping.is_empty() {
+            index
+        } else {
+            (*self.mapping.get(index)?)?
+        };
+        let degree_cents = if degree == 0 {
+            0.0
+        } else {
+            *scale.steps_cents.get(degree - 1)?
+        };
+        let reference_cents = periods as f32 * period_cents + degree_cents;
+        Some(self.reference_frequency / 2f32.powf(reference_cents / 1200.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCL_22_EDO: &str = "\
+! 22edo.scl
+!
+22 tone equal temperament
+ 22
+!
+ 54.54545
+ 109.09091
+ 163.63636
+ 218.18182
+ 272.72727
+ 327.27273
+ 381.81818
+ 436.36364
+ 490.90909
+ 545.45455
+ 600.00000
+ 654.54545
+ 709.09091
+ 763.63636
+ 818.18182
+ 872.72727
+ 927.27273
+ 981.81818
+ 1036.36364
+ 1090.90909
+ 1145.45455
+ 2/1
+";
+
+    #[test]
+    fn scl_parses_cents_and_ratio_lines() {
+        let scale = parse_scl(SCL_22_EDO).unwrap();
+        assert_eq!(scale.description, "22 tone equal temperament");
+        assert_eq!(scale.steps_cents.len(), 22);
+        assert!((scale.steps_cents[0] - 54.54545).abs() < 1e-3);
+        // The 2/1 ratio comes out as a 1200-cent period.
+        assert!((scale.steps_cents[21] - 1200.0).abs() < 0.01);
+
+        // Malformed files are rejected rather than truncated.
+        assert!(parse_scl("").is_err());
+        assert!(parse_scl("desc\n2\n100.0\n").is_err());
+        assert!(parse_scl("desc\n1\n0/3\n").is_err());
+    }
+
+    #[test]
+    fn lookup_snaps_to_the_nearest_degree() {
+        let scale = parse_scl(SCL_22_EDO).unwrap();
+        let base = 261.63;
+        // A frequency slightly sharp of degree 3 still reads as degree 3.
+        let exact = base * 2f32.powf(163.63636 / 1200.0);
+        let (label, target) = scale.lookup(exact * 1.003, base).unwrap();
+        assert_eq!(label, "3\\22 p4");
+        assert!((target - exact).abs() < 0.01, "target was {}", target);
+        // The period boundary rolls over to degree 0 of the next period.
+        let (label, target) = scale.lookup(base * 2.001, base).unwrap();
+        assert_eq!(label, "0\\22 p5");
+        assert!((target - base * 2.0).abs() < 0.01);
+        assert!(scale.lookup(0.0, base).is_none());
+    }
+
+    #[test]
+    fn kbm_anchors_the_base_frequency() {
+        let scale = parse_scl(SCL_22_EDO).unwrap();
+        // Linear mapping with A above middle C as the 440 Hz reference:
+        // with 22 degrees per octave the reference sits 13 degrees up.
+        let kbm = "\
+! a440.kbm
+22
+0
+127
+60
+73
+440.0
+22
+";
+        let mapping = parse_kbm(kbm).unwrap();
+        assert_eq!(mapping.reference_note, 73);
+        assert!(mapping.mapping.is_empty());
+        let base = mapping.base_frequency(&scale).unwrap();
+        let expected = 440.0 / 2f32.powf((13.0 * 54.54545) / 1200.0);
+        assert!((base - expected).abs() < 0.01, "base was {}", base);
+        assert!(parse_kbm("12\n0\n").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Browser entry point for the tuner.
+//!
+//! The DSP in this crate is platform-independent; this module adds the glue
+//! eframe needs to run it as a web page. Microphone capture still goes
+//! through cpal, whose wasm backend wraps the Web Audio API, and analysis
+//! runs inside the egui update loop instead of a background thread because
+//! browsers have no blocking sleep.
+//!
+//! Building and serving:
+//!
+//! ```text
+//! rustup target add wasm32-unknown-unknown
+//! cargo build --lib --target wasm32-unknown-unknown --release
+//! wasm-bindgen --target web --out-dir dist \
+//!     target/wasm32-unknown-unknown/release/rustique.wasm
+//! ```
+//!
+//! then serve `dist/` from any static file server over HTTPS (browsers only
+//! expose microphones on secure origins) with an `index.html` that calls
+//! `new WebHandle().start(canvas)`. The `trunk` tool automates these steps.
+//!
+//! Note that the desktop binary in `main.rs` is not built for wasm (its
+//! MIDI and filesystem features have no browser equivalent); build with
+//! `--lib` as shown above.
+
+use crate::{
+    DetectionMethod, PitchSmoother, StftProcessor, Temperament, aggregate_magnitudes,
+    cents_offset, cepstrum_pitch, downmix_to_mono, frequency_to_note, harmonic_product_spectrum,
+    refine_peak_bin, zero_crossing_pitch,
+};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use eframe::egui;
+use eframe::wasm_bindgen::{self, prelude::*};
+use std::sync::{Arc, Mutex};
+
+const WINDOW_SIZE: usize = 4096;
+const HOP_SIZE: usize = 2048;
+
+/// Handle exposed to JavaScript for starting the app on a canvas.
+#[wasm_bindgen]
+pub struct WebHandle {
+    runner: eframe::WebRunner,
+}
+
+#[wasm_bindgen]
+impl WebHandle {
+    #[allow(clippy::new_without_default)]
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        WebHandle {
+            runner: eframe::WebRunner::new(),
+        }
+    }
+
+    /// Start the tuner on the given canvas element.
+    #[wasm_bindgen]
+    pub async fn start(
+        &self,
+        canvas: eframe::web_sys::HtmlCanvasElement,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        self.runner
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|_cc| Ok(Box::new(WebTuner::default()))),
+            )
+            .await
+    }
+}
+
+/// Browser build of the tuner: the same detection pipeline as the desktop
+/// app, driven from the repaint loop rather than a thread.
+struct WebTuner {
+    audio_data: Arc<Mutex<Vec<f32>>>,
+    stream: Option<cpal::Stream>,
+    stream_error: Option<String>,
+    sample_rate: usize,
+    stft_processor: StftProcessor,
+    pitch_smoother: PitchSmoother,
+    detection_method: DetectionMethod,
+    detected_note: String,
+    detected_freq: f32,
+    detected_cents: f32,
+}
+
+impl Default for WebTuner {
+    fn default() -> Self {
+        WebTuner {
+            audio_data: Arc::new(Mutex::new(Vec::new())),
+            stream: None,
+            stream_error: None,
+            sample_rate: 44100,
+            stft_processor: StftProcessor::new(WINDOW_SIZE, HOP_SIZE),
+            pitch_smoother: PitchSmoother::new(5, 100.0),
+            detection_method: DetectionMethod::HarmonicProduct,
+            detected_note: "—".to_string(),
+            detected_freq: 0.0,
+            detected_cents: 0.0,
+        }
+    }
+}
+
+impl WebTuner {
+    /// Ask the browser for microphone access and start feeding the buffer.
+    /// Must be called from a user gesture, which is why the GUI gates it
+    /// behind a button.
+    fn start_microphone(&mut self) {
+        self.stream_error = None;
+        let host = cpal::default_host();
+        let Some(device) = host.default_input_device() else {
+            self.stream_error = Some("No microphone available".to_string());
+            return;
+        };
+        let config = match device.default_input_config() {
+            Ok(config) => config,
+            Err(err) => {
+                self.stream_error = Some(format!("Could not read input configuration: {}", err));
+                return;
+            }
+        };
+        self.sample_rate = config.sample_rate().0 as usize;
+        let channels = config.channels() as usize;
+        let audio_data = self.audio_data.clone();
+        let stream = device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _| {
+                let mono = downmix_to_mono(data, channels);
+                if let Ok(mut buffer) = audio_data.lock() {
+                    buffer.extend_from_slice(&mono);
+                }
+            },
+            |_err| {},
+            None,
+        );
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = stream.play() {
+                    self.stream_error = Some(format!("Could not start the stream: {}", err));
+                    return;
+                }
+                self.stream = Some(stream);
+            }
+            Err(err) => {
+                self.stream_error = Some(format!("Could not open the stream: {}", err));
+            }
+        }
+    }
+
+    /// Run one detection pass over whatever audio has arrived since the
+    /// last repaint.
+    fn analyze_pending_audio(&mut self) {
+        let mut buffer = match self.audio_data.lock() {
+            Ok(buffer) => buffer,
+            Err(_) => return,
+        };
+        if buffer.len() < WINDOW_SIZE {
+            return;
+        }
+        let time_window: Vec<f32> = buffer[..WINDOW_SIZE].to_vec();
+        let magnitudes = self.stft_processor.magnitudes(&buffer);
+        let drain_len = buffer.len() - (WINDOW_SIZE - HOP_SIZE).min(buffer.len());
+        buffer.drain(..drain_len);
+        drop(buffer);
+
+        let averaged = aggregate_magnitudes(&magnitudes, crate::FrameAggregation::Mean);
+        let freq_resolution = self.sample_rate as f32 / WINDOW_SIZE as f32;
+        let dominant_freq = match self.detection_method {
+            DetectionMethod::SpectralPeak => averaged
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(bin, _)| refine_peak_bin(&averaged, bin) * freq_resolution),
+            DetectionMethod::HarmonicProduct => {
+                let hps = harmonic_product_spectrum(&averaged, 3);
+                hps.iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .map(|(bin, _)| refine_peak_bin(&averaged, bin) * freq_resolution)
+            }
+            DetectionMethod::Cepstrum => {
+                cepstrum_pitch(&averaged, self.sample_rate, WINDOW_SIZE)
+            }
+            DetectionMethod::ZeroCrossing => zero_crossing_pitch(&time_window, self.sample_rate),
+            DetectionMethod::Yin => {
+                crate::pitch::yin_pitch(&time_window, self.sample_rate, 0.15)
+            }
+            DetectionMethod::Mpm => {
+                crate::pitch::mpm_pitch(&time_window, self.sample_rate, 0.8)
+            }
+        };
+        let Some(dominant_freq) = dominant_freq else {
+            return;
+        };
+        let smoothed = self.pitch_smoother.push(dominant_freq);
+        if let Some((note, target)) = frequency_to_note(smoothed, Temperament::Equal, 0) {
+            self.detected_note = note;
+            self.detected_freq = smoothed;
+            self.detected_cents = cents_offset(smoothed, target);
+        }
+    }
+}
+
+impl eframe::App for WebTuner {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        ctx.request_repaint();
+        self.analyze_pending_audio();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Rustique Tuner");
+            if self.stream.is_none() {
+                if ui.button("Start microphone").clicked() {
+                    self.start_microphone();
+                }
+                if let Some(error) = &self.stream_error {
+                    ui.colored_label(egui::Color32::from_rgb(220, 60, 60), error);
+                }
+                return;
+            }
+            egui::ComboBox::from_label("Detection method")
+                .selected_text(self.detection_method.name())
+                .show_ui(ui, |ui| {
+                    for option in DetectionMethod::ALL {
+                        ui.selectable_value(&mut self.detection_method, option, option.name());
+                    }
+                });
+            ui.label(format!("Detected note: {}", self.detected_note));
+            ui.label(format!("Frequency: {:.2} Hz", self.detected_freq));
+            ui.label(format!("Offset: {:+.1} cents", self.detected_cents));
+        });
+    }
+}
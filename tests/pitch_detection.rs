@@ -0,0 +1,85 @@
+use rustique::{
+    Temperament, analyze_pitch_track, detect_pitch, frequency_to_note, read_wav, write_wav,
+};
+use std::f32::consts::PI;
+
+fn sine(frequency: f32, sample_rate: usize, samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|i| (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin() * 0.5)
+        .collect()
+}
+
+#[test]
+fn detects_a4_from_wav_fixture() {
+    let sample_rate = 44100;
+    let samples = sine(440.0, sample_rate, 4096 * 3);
+    let path = std::env::temp_dir().join("rustique_fixture_a4.wav");
+    let path = path.to_str().unwrap().to_string();
+    write_wav(&path, &samples, sample_rate).unwrap();
+
+    let (read_rate, read_samples) = read_wav(&path).unwrap();
+    assert_eq!(read_rate, sample_rate);
+    assert_eq!(read_samples.len(), samples.len());
+
+    let freq = detect_pitch(&read_samples, read_rate, 4096, 2048).unwrap();
+    let bin_width = sample_rate as f32 / 4096.0;
+    assert!(
+        (freq - 440.0).abs() <= bin_width,
+        "detected {} Hz for a 440 Hz tone",
+        freq
+    );
+    let (note, _) = frequency_to_note(freq, Temperament::Equal, 0).unwrap();
+    assert_eq!(note, "A4");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn detects_c4_from_generated_tone() {
+    let sample_rate = 44100;
+    let samples = sine(261.63, sample_rate, 4096 * 3);
+    let freq = detect_pitch(&samples, sample_rate, 4096, 2048).unwrap();
+    let (note, _) = frequency_to_note(freq, Temperament::Equal, 0).unwrap();
+    assert_eq!(note, "C4");
+}
+
+#[test]
+fn pitch_track_follows_a_glissando_upward() {
+    let sample_rate = 44100;
+    let seconds = 3.0;
+    let samples_len = (sample_rate as f32 * seconds) as usize;
+    // Sweep 220 Hz to 440 Hz with a continuous phase so there are no
+    // clicks for the analysis to trip over.
+    let mut phase = 0.0f32;
+    let samples: Vec<f32> = (0..samples_len)
+        .map(|i| {
+            let progress = i as f32 / samples_len as f32;
+            let freq = 220.0 + 220.0 * progress;
+            phase += 2.0 * PI * freq / sample_rate as f32;
+            phase.sin() * 0.5
+        })
+        .collect();
+
+    let track = analyze_pitch_track(&samples, sample_rate, 4096, 2048);
+    assert!(track.len() > 10, "only {} frames", track.len());
+    let bin_width = sample_rate as f32 / 4096.0;
+    for pair in track.windows(2) {
+        assert!(
+            pair[1].frequency >= pair[0].frequency - bin_width,
+            "pitch fell from {} Hz to {} Hz at {} s",
+            pair[0].frequency,
+            pair[1].frequency,
+            pair[1].time
+        );
+        assert!(pair[1].time > pair[0].time);
+    }
+    assert!(track.first().unwrap().frequency < 280.0);
+    assert!(track.last().unwrap().frequency > 380.0);
+    assert_eq!(track.first().unwrap().note.as_deref(), Some("A3"));
+}
+
+#[test]
+fn short_buffer_yields_no_pitch() {
+    let samples = sine(440.0, 44100, 1000);
+    assert!(detect_pitch(&samples, 44100, 4096, 2048).is_none());
+}